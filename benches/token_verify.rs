@@ -0,0 +1,66 @@
+//! token verify throughput, parsing the public key on every call versus the
+//! precomputed key pair held by the key registry
+//!
+//! run with `cargo bench --bench token_verify`
+
+use auth_rs::token::keys::KeyPair;
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation};
+use openssl::rsa::Rsa;
+use serde::{Deserialize, Serialize};
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const ITERATIONS: u32 = 2_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    exp: usize,
+}
+
+fn run(name: &str, mut verify: impl FnMut()) {
+    // warm up caches before timing
+    for _ in 0..ITERATIONS / 10 {
+        verify();
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        verify();
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{:<24} {:>10.2?}/verify {:>10.0} verifies/s",
+        name,
+        elapsed / ITERATIONS,
+        ITERATIONS as f64 / elapsed.max(Duration::from_nanos(1)).as_secs_f64()
+    );
+}
+
+fn main() {
+    let rsa = Rsa::generate(2048).unwrap();
+    let private_key = rsa.private_key_to_pem().unwrap();
+    let public_key = rsa.public_key_to_pem().unwrap();
+    let pair = KeyPair::from_rsa_pem(&private_key, &public_key).unwrap();
+
+    let claims = Claims {
+        sub: ulid::Ulid::new().to_string(),
+        exp: usize::MAX / 2,
+    };
+    let token =
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &pair.encoding).unwrap();
+    let validation = Validation::new(Algorithm::RS256);
+
+    run("parse pem per call", || {
+        let key = DecodingKey::from_rsa_pem(&public_key).unwrap();
+        black_box(jsonwebtoken::decode::<Claims>(black_box(&token), &key, &validation).unwrap());
+    });
+    run("precomputed key pair", || {
+        black_box(
+            jsonwebtoken::decode::<Claims>(black_box(&token), &pair.decoding, &validation).unwrap(),
+        );
+    });
+}
@@ -1,5 +1,13 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    tonic_build::compile_protos("proto/auth.proto")?;
-    tonic_build::compile_protos("proto/admin.proto")?;
+    let mut config = prost_build::Config::new();
+    if std::env::var_os("PROTOC").is_none() {
+        config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+    }
+
+    tonic_build::configure().compile_protos_with_config(
+        config,
+        &["proto/auth.proto", "proto/admin.proto"],
+        &["proto"],
+    )?;
     Ok(())
 }
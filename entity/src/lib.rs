@@ -4,7 +4,13 @@ pub mod prelude;
 
 pub mod admin;
 pub mod admin_api_key;
+pub mod directory;
+pub mod login_risk;
+pub mod permission;
 pub mod provider;
+pub mod role;
+pub mod role_permission;
 pub mod session;
 pub mod user;
 pub mod user_provider;
+pub mod user_role;
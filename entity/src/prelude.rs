@@ -2,7 +2,13 @@
 
 pub use super::admin::Entity as Admin;
 pub use super::admin_api_key::Entity as AdminApiKey;
+pub use super::directory::Entity as Directory;
+pub use super::login_risk::Entity as LoginRisk;
+pub use super::permission::Entity as Permission;
 pub use super::provider::Entity as Provider;
+pub use super::role::Entity as Role;
+pub use super::role_permission::Entity as RolePermission;
 pub use super::session::Entity as Session;
 pub use super::user::Entity as User;
 pub use super::user_provider::Entity as UserProvider;
+pub use super::user_role::Entity as UserRole;
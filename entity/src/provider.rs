@@ -13,10 +13,18 @@ pub struct Model {
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::directory::Entity")]
+    Directory,
     #[sea_orm(has_many = "super::user_provider::Entity")]
     UserProvider,
 }
 
+impl Related<super::directory::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Directory.def()
+    }
+}
+
 impl Related<super::user_provider::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::UserProvider.def()
@@ -26,6 +26,11 @@ pub struct Model {
     #[sea_orm(column_type = "Decimal(Some((11, 8)))", nullable)]
     pub lon: Option<Decimal>,
     pub map_url: Option<String>,
+    pub remember_me: bool,
+    pub last_seen_at: i32,
+    pub last_ip_address: Option<String>,
+    pub refresh_count: i32,
+    pub asn: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
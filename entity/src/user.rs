@@ -16,14 +16,28 @@ pub struct Model {
     pub photo_url: Option<String>,
     pub is_email_verified: bool,
     pub is_two_factor_enabled: bool,
+    pub tokens_valid_after: i64,
+    pub profile_version: i32,
+    pub password_reset_required: bool,
+    pub max_sessions: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::login_risk::Entity")]
+    LoginRisk,
     #[sea_orm(has_many = "super::session::Entity")]
     Session,
     #[sea_orm(has_many = "super::user_provider::Entity")]
     UserProvider,
+    #[sea_orm(has_many = "super::user_role::Entity")]
+    UserRole,
+}
+
+impl Related<super::login_risk::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LoginRisk.def()
+    }
 }
 
 impl Related<super::session::Entity> for Entity {
@@ -38,6 +52,12 @@ impl Related<super::user_provider::Entity> for Entity {
     }
 }
 
+impl Related<super::user_role::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserRole.def()
+    }
+}
+
 impl Related<super::provider::Entity> for Entity {
     fn to() -> RelationDef {
         super::user_provider::Relation::Provider.def()
@@ -47,4 +67,13 @@ impl Related<super::provider::Entity> for Entity {
     }
 }
 
+impl Related<super::role::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::user_role::Relation::Role.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::user_role::Relation::User.def().rev())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
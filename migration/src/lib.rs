@@ -6,6 +6,21 @@ mod m20250303_051527_create_table_session;
 mod m20250303_051626_create_table_user_provider;
 mod m20250314_124122_admin;
 mod m20250314_124135_admin_api_key;
+mod m20250322_101512_create_table_directory;
+mod m20250326_081204_create_table_role;
+mod m20250326_081219_create_table_permission;
+mod m20250326_081237_create_table_role_permission;
+mod m20250326_081251_create_table_user_role;
+mod m20250328_091406_alter_table_user_add_tokens_valid_after;
+mod m20250329_143027_alter_table_user_add_profile_version;
+mod m20250330_102245_alter_table_session_add_remember_me;
+mod m20250331_164510_alter_table_session_add_activity;
+mod m20250401_083015_alter_table_user_add_password_reset_required;
+mod m20250402_091733_alter_table_session_add_asn;
+mod m20250402_091748_create_table_login_risk;
+mod m20250403_102634_alter_table_user_add_max_sessions;
+mod m20250404_093512_alter_table_user_alter_tokens_valid_after;
+mod m20250405_081322_alter_table_user_tokens_valid_after_millis;
 
 pub struct Migrator;
 
@@ -19,6 +34,21 @@ impl MigratorTrait for Migrator {
             Box::new(m20250303_051626_create_table_user_provider::Migration),
             Box::new(m20250314_124122_admin::Migration),
             Box::new(m20250314_124135_admin_api_key::Migration),
+            Box::new(m20250322_101512_create_table_directory::Migration),
+            Box::new(m20250326_081204_create_table_role::Migration),
+            Box::new(m20250326_081219_create_table_permission::Migration),
+            Box::new(m20250326_081237_create_table_role_permission::Migration),
+            Box::new(m20250326_081251_create_table_user_role::Migration),
+            Box::new(m20250328_091406_alter_table_user_add_tokens_valid_after::Migration),
+            Box::new(m20250329_143027_alter_table_user_add_profile_version::Migration),
+            Box::new(m20250330_102245_alter_table_session_add_remember_me::Migration),
+            Box::new(m20250331_164510_alter_table_session_add_activity::Migration),
+            Box::new(m20250401_083015_alter_table_user_add_password_reset_required::Migration),
+            Box::new(m20250402_091733_alter_table_session_add_asn::Migration),
+            Box::new(m20250402_091748_create_table_login_risk::Migration),
+            Box::new(m20250403_102634_alter_table_user_add_max_sessions::Migration),
+            Box::new(m20250404_093512_alter_table_user_alter_tokens_valid_after::Migration),
+            Box::new(m20250405_081322_alter_table_user_tokens_valid_after_millis::Migration),
         ]
     }
 }
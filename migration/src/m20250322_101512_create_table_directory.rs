@@ -0,0 +1,114 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20250303_051455_create_table_provider::Provider;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum Directory {
+    Table,
+    Id,
+    ProviderId,
+    Domain,
+    Url,
+    BindDn,
+    BaseDn,
+    EmailAttribute,
+    UsernameAttribute,
+    NameAttribute,
+}
+
+const IDX_PROVIDER_ID: &str = "idx_directory_provider_id";
+const FK_PROVIDER_ID: &str = "fk_directory_provider_id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Directory::Table)
+                    .if_not_exists()
+                    .col(
+                        string(Directory::Id)
+                            .char()
+                            .char_len(26)
+                            .primary_key()
+                            .extra("DEFAULT public.gen_ulid()"),
+                    )
+                    .col(string(Directory::ProviderId).string_len(150))
+                    .col(string(Directory::Domain).string_len(255).unique_key())
+                    .col(string(Directory::Url).string_len(255))
+                    .col(string(Directory::BindDn).string_len(255))
+                    .col(string(Directory::BaseDn).string_len(255))
+                    .col(
+                        string(Directory::EmailAttribute)
+                            .string_len(100)
+                            .extra("DEFAULT 'mail'"),
+                    )
+                    .col(
+                        string(Directory::UsernameAttribute)
+                            .string_len(100)
+                            .extra("DEFAULT 'uid'"),
+                    )
+                    .col(
+                        string(Directory::NameAttribute)
+                            .string_len(100)
+                            .extra("DEFAULT 'cn'"),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(Directory::Table)
+                    .col(Directory::ProviderId)
+                    .name(IDX_PROVIDER_ID)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(FK_PROVIDER_ID)
+                    .from(Directory::Table, Directory::ProviderId)
+                    .to(Provider::Table, Provider::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_PROVIDER_ID)
+                    .table(Directory::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name(FK_PROVIDER_ID)
+                    .table(Directory::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Directory::Table).if_exists().to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+pub enum Role {
+    Table,
+    Id,
+    Name,
+    Description,
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Role::Table)
+                    .if_not_exists()
+                    .col(
+                        string(Role::Id)
+                            .char()
+                            .char_len(26)
+                            .primary_key()
+                            .extra("DEFAULT public.gen_ulid()"),
+                    )
+                    .col(string(Role::Name).string_len(100).unique_key())
+                    .col(text(Role::Description))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Role::Table).if_exists().to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
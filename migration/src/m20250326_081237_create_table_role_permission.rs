@@ -0,0 +1,137 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::{
+    m20250326_081204_create_table_role::Role, m20250326_081219_create_table_permission::Permission,
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum RolePermission {
+    Table,
+    RoleId,
+    PermissionId,
+}
+
+const IDX_ROLE_ID: &str = "idx_role_permission_role_id";
+const IDX_PERMISSION_ID: &str = "idx_role_permission_permission_id";
+
+const FK_ROLE_ID: &str = "fk_role_permission_role_id";
+const FK_PERMISSION_ID: &str = "fk_role_permission_permission_id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(RolePermission::Table)
+                    .if_not_exists()
+                    .col(string(RolePermission::RoleId).char().char_len(26))
+                    .col(string(RolePermission::PermissionId).char().char_len(26))
+                    .primary_key(
+                        Index::create()
+                            .col(RolePermission::RoleId)
+                            .col(RolePermission::PermissionId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(RolePermission::Table)
+                    .col(RolePermission::RoleId)
+                    .name(IDX_ROLE_ID)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(RolePermission::Table)
+                    .col(RolePermission::PermissionId)
+                    .name(IDX_PERMISSION_ID)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(FK_ROLE_ID)
+                    .from(RolePermission::Table, RolePermission::RoleId)
+                    .to(Role::Table, Role::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(FK_PERMISSION_ID)
+                    .from(RolePermission::Table, RolePermission::PermissionId)
+                    .to(Permission::Table, Permission::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_ROLE_ID)
+                    .table(RolePermission::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_PERMISSION_ID)
+                    .table(RolePermission::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name(FK_ROLE_ID)
+                    .table(RolePermission::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name(FK_PERMISSION_ID)
+                    .table(RolePermission::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(RolePermission::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
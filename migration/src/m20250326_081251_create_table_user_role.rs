@@ -0,0 +1,128 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::{m20250302_192622_create_table_user::User, m20250326_081204_create_table_role::Role};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum UserRole {
+    Table,
+    UserId,
+    RoleId,
+    AssignedAt,
+}
+
+const IDX_USER_ID: &str = "idx_user_role_user_id";
+const IDX_ROLE_ID: &str = "idx_user_role_role_id";
+
+const FK_USER_ID: &str = "fk_user_role_user_id";
+const FK_ROLE_ID: &str = "fk_user_role_role_id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserRole::Table)
+                    .if_not_exists()
+                    .col(string(UserRole::UserId).char().char_len(26))
+                    .col(string(UserRole::RoleId).char().char_len(26))
+                    .col(date_time(UserRole::AssignedAt).extra("DEFAULT CURRENT_TIMESTAMP"))
+                    .primary_key(Index::create().col(UserRole::UserId).col(UserRole::RoleId))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(UserRole::Table)
+                    .col(UserRole::UserId)
+                    .name(IDX_USER_ID)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(UserRole::Table)
+                    .col(UserRole::RoleId)
+                    .name(IDX_ROLE_ID)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(FK_USER_ID)
+                    .from(UserRole::Table, UserRole::UserId)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(FK_ROLE_ID)
+                    .from(UserRole::Table, UserRole::RoleId)
+                    .to(Role::Table, Role::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_USER_ID)
+                    .table(UserRole::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_ROLE_ID)
+                    .table(UserRole::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name(FK_USER_ID)
+                    .table(UserRole::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name(FK_ROLE_ID)
+                    .table(UserRole::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(UserRole::Table).if_exists().to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
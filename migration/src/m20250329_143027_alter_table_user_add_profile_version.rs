@@ -0,0 +1,71 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum User {
+    Table,
+    ProfileVersion,
+}
+
+// bumps the version whenever a column embedded in the session token changes
+const CREATE_FUNCTION: &str = r#"
+CREATE OR REPLACE FUNCTION auth.bump_user_profile_version() RETURNS trigger AS $$
+BEGIN
+    IF (NEW.email, NEW.username, NEW.name, NEW.photo_url, NEW.is_email_verified, NEW.is_two_factor_enabled)
+        IS DISTINCT FROM
+       (OLD.email, OLD.username, OLD.name, OLD.photo_url, OLD.is_email_verified, OLD.is_two_factor_enabled)
+    THEN
+        NEW.profile_version := OLD.profile_version + 1;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+"#;
+
+const CREATE_TRIGGER: &str = r#"
+CREATE TRIGGER trg_user_profile_version
+    BEFORE UPDATE ON auth."user"
+    FOR EACH ROW EXECUTE FUNCTION auth.bump_user_profile_version();
+"#;
+
+const DROP_TRIGGER: &str = r#"DROP TRIGGER IF EXISTS trg_user_profile_version ON auth."user";"#;
+const DROP_FUNCTION: &str = "DROP FUNCTION IF EXISTS auth.bump_user_profile_version();";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column_if_not_exists(integer(User::ProfileVersion).unsigned().default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        let db = manager.get_connection();
+        db.execute_unprepared(CREATE_FUNCTION).await?;
+        db.execute_unprepared(CREATE_TRIGGER).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared(DROP_TRIGGER).await?;
+        db.execute_unprepared(DROP_FUNCTION).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::ProfileVersion)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum Session {
+    Table,
+    LastSeenAt,
+    LastIpAddress,
+    RefreshCount,
+}
+
+// existing sessions were last seen when they logged in
+const BACKFILL: &str = r#"
+UPDATE auth.session
+SET last_seen_at = login_at, last_ip_address = ip_address
+WHERE last_seen_at = 0;
+"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .add_column_if_not_exists(integer(Session::LastSeenAt).unsigned().default(0))
+                    .add_column_if_not_exists(string_null(Session::LastIpAddress))
+                    .add_column_if_not_exists(integer(Session::RefreshCount).unsigned().default(0))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(BACKFILL)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Session::Table)
+                    .drop_column(Session::LastSeenAt)
+                    .drop_column(Session::LastIpAddress)
+                    .drop_column(Session::RefreshCount)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+use crate::m20250302_192622_create_table_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[derive(DeriveIden)]
+enum LoginRisk {
+    Table,
+    Id,
+    UserId,
+    IpAddress,
+    Country,
+    Asn,
+    Lat,
+    Lon,
+    Score,
+    Signals,
+    Decision,
+    CreatedAt,
+}
+
+const IDX_USER_ID: &str = "idx_login_risk_user_id";
+const IDX_CREATED_AT: &str = "idx_login_risk_created_at";
+
+const FK_USER_ID: &str = "fk_login_risk_user_id";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LoginRisk::Table)
+                    .if_not_exists()
+                    .col(
+                        string(LoginRisk::Id)
+                            .char()
+                            .char_len(26)
+                            .primary_key()
+                            .extra("DEFAULT public.gen_ulid()"),
+                    )
+                    .col(string(LoginRisk::UserId).char().char_len(26))
+                    .col(string(LoginRisk::IpAddress).string_len(45))
+                    .col(string_null(LoginRisk::Country).string_len(80))
+                    .col(string_null(LoginRisk::Asn).string_len(20))
+                    .col(float_null(LoginRisk::Lat).decimal_len(10, 8))
+                    .col(float_null(LoginRisk::Lon).decimal_len(11, 8))
+                    .col(integer(LoginRisk::Score))
+                    .col(string(LoginRisk::Signals).string_len(255))
+                    .col(string(LoginRisk::Decision).string_len(20))
+                    .col(integer(LoginRisk::CreatedAt).unsigned())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .table(LoginRisk::Table)
+                    .col(LoginRisk::UserId)
+                    .name(IDX_USER_ID)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .table(LoginRisk::Table)
+                    .col(LoginRisk::CreatedAt)
+                    .name(IDX_CREATED_AT)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name(FK_USER_ID)
+                    .from(LoginRisk::Table, LoginRisk::UserId)
+                    .to(User::Table, User::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .on_update(ForeignKeyAction::Restrict)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_USER_ID)
+                    .table(LoginRisk::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name(IDX_CREATED_AT)
+                    .table(LoginRisk::Table)
+                    .if_exists()
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name(FK_USER_ID)
+                    .table(LoginRisk::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(LoginRisk::Table).if_exists().to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// token epochs moved from seconds to milliseconds
+const UP: &str = r#"
+UPDATE auth."user"
+SET tokens_valid_after = tokens_valid_after * 1000
+WHERE tokens_valid_after > 0;
+"#;
+
+// rounds up so no token revoked in milliseconds becomes valid again
+const DOWN: &str = r#"
+UPDATE auth."user"
+SET tokens_valid_after = (tokens_valid_after + 999) / 1000
+WHERE tokens_valid_after > 0;
+"#;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.get_connection().execute_unprepared(UP).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.get_connection().execute_unprepared(DOWN).await?;
+
+        Ok(())
+    }
+}
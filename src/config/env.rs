@@ -1,17 +1,81 @@
 use crate::{
     error::AppError,
-    util::{deserialize_arc_str, deserialize_base64},
+    token::keys::Keys,
+    util::{
+        deserialize_arc_str, deserialize_audience_map, deserialize_base64,
+        deserialize_optional_base64,
+    },
 };
 use dotenvy::dotenv;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::process::exit;
 use std::sync::Arc;
 use time::Duration;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStoreKind {
+    #[default]
+    Redis,
+    Memory,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessTokenMode {
+    #[default]
+    Jwt,
+    Opaque,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenFormat {
+    #[default]
+    Jwt,
+    Paseto,
+}
+
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GeoIpProvider {
+    #[default]
+    Ipinfo,
+    Mmdb,
+    None,
+}
+
+/// what happens to a login the risk engine considers high risk
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskAction {
+    #[default]
+    Challenge,
+    Block,
+}
+
+/// what happens to a login that would exceed the session limit of a user
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionLimitAction {
+    Reject,
+    #[default]
+    Evict,
+}
 
 #[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "validate_session_lifetime"))]
+#[validate(schema(function = "validate_geoip"))]
+#[validate(schema(function = "validate_exchange_targets"))]
 pub struct Env {
+    /// parsed in `Env::new` so unusable keys stop the boot along with the
+    /// rest of the environment
+    #[serde(skip)]
+    keys: Option<Arc<Keys>>,
+
     #[validate(length(min = 1, message = "DATABASE_URL is required"))]
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub database_url: Arc<str>,
@@ -28,6 +92,18 @@ pub struct Env {
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub redis_schema: Arc<str>,
 
+    #[serde(default)]
+    pub token_store: TokenStoreKind,
+
+    #[serde(default)]
+    pub access_token_mode: AccessTokenMode,
+
+    /// revocations only evict the cache of the instance handling them, other
+    /// instances keep accepting a revoked token for up to this many seconds
+    #[validate(range(max = 5, message = "OPAQUE_TOKEN_CACHE_TTL must be at most 5 seconds"))]
+    #[serde(default)]
+    pub opaque_token_cache_ttl: usize,
+
     #[validate(custom(function = "envmode::verify"))]
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub env: Arc<str>,
@@ -36,10 +112,61 @@ pub struct Env {
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub domain: Arc<str>,
 
-    #[validate(length(min = 1, message = "IPINFO_API_KEY is required"))]
-    #[serde(deserialize_with = "deserialize_arc_str")]
+    #[serde(default)]
+    pub geoip_provider: GeoIpProvider,
+
+    #[serde(default, deserialize_with = "deserialize_arc_str")]
     pub ipinfo_api_key: Arc<str>,
 
+    #[serde(default)]
+    pub geoip_city_database: Option<String>,
+
+    #[serde(default)]
+    pub geoip_asn_database: Option<String>,
+
+    #[validate(range(
+        max = TryInto::<usize>::try_into(Duration::days(30).whole_seconds()).unwrap(),
+        message = "GEOIP_CACHE_TTL must be at most 30 days"
+    ))]
+    #[serde(default = "default_geoip_cache_ttl")]
+    pub geoip_cache_ttl: usize,
+
+    #[validate(range(
+        min = 1,
+        max = 100,
+        message = "GEOIP_BREAKER_THRESHOLD must be between 1 and 100"
+    ))]
+    #[serde(default = "default_geoip_breaker_threshold")]
+    pub geoip_breaker_threshold: usize,
+
+    #[validate(range(
+        min = 1,
+        max = 3600,
+        message = "GEOIP_BREAKER_COOLDOWN must be between 1 second and 1 hour"
+    ))]
+    #[serde(default = "default_geoip_breaker_cooldown")]
+    pub geoip_breaker_cooldown: u64,
+
+    #[validate(range(min = 1, max = 64, message = "JOB_WORKERS must be between 1 and 64"))]
+    #[serde(default = "default_job_workers")]
+    pub job_workers: usize,
+
+    #[validate(range(
+        min = 1,
+        max = 20,
+        message = "JOB_MAX_ATTEMPTS must be between 1 and 20"
+    ))]
+    #[serde(default = "default_job_max_attempts")]
+    pub job_max_attempts: u32,
+
+    #[validate(range(
+        min = 60,
+        max = 86400,
+        message = "SWEEPER_INTERVAL must be between 1 minute and 1 day"
+    ))]
+    #[serde(default = "default_sweeper_interval")]
+    pub sweeper_interval: u64,
+
     #[validate(length(min = 1, message = "RESEND_EMAIL is required"))]
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub resend_email: Arc<str>,
@@ -48,10 +175,49 @@ pub struct Env {
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub resend_api: Arc<str>,
 
+    #[validate(url(message = "MAGIC_LINK_URL must be a valid url"))]
+    #[serde(deserialize_with = "deserialize_arc_str")]
+    pub magic_link_url: Arc<str>,
+
+    #[validate(url(message = "PASSWORD_RESET_URL must be a valid url"))]
+    #[serde(deserialize_with = "deserialize_arc_str")]
+    pub password_reset_url: Arc<str>,
+
+    #[validate(url(message = "SESSION_REPORT_URL must be a valid url"))]
+    #[serde(deserialize_with = "deserialize_arc_str")]
+    pub session_report_url: Arc<str>,
+
+    #[serde(default)]
+    pub high_risk_login_action: RiskAction,
+
+    #[validate(range(max = 1000, message = "MAX_SESSIONS must be at most 1000"))]
+    #[serde(default)]
+    pub max_sessions: usize,
+
+    #[serde(default)]
+    pub session_limit_action: SessionLimitAction,
+
+    #[validate(url(message = "DPOP_ORIGIN must be a valid url"))]
+    #[serde(default)]
+    pub dpop_origin: Option<String>,
+
     #[validate(length(min = 1, message = "RESEND_API_KEY is required"))]
     #[serde(deserialize_with = "deserialize_arc_str")]
     pub route_secret: Arc<str>,
 
+    #[validate(length(min = 1, message = "TOKEN_ISSUER is required"))]
+    #[serde(deserialize_with = "deserialize_arc_str")]
+    pub token_issuer: Arc<str>,
+
+    #[validate(length(
+        min = 1,
+        message = "TOKEN_AUDIENCES must contain at least one audience"
+    ))]
+    pub token_audiences: Vec<String>,
+
+    #[serde(default, deserialize_with = "deserialize_audience_map")]
+    pub token_exchange_targets: HashMap<String, Vec<String>>,
+
     #[validate(length(min = 1, message = "REFRESH_TOKEN_PRIVATE_KEY is required"))]
     #[serde(deserialize_with = "deserialize_base64")]
     pub refresh_token_private_key: Arc<Vec<u8>>,
@@ -84,6 +250,30 @@ pub struct Env {
     #[serde(deserialize_with = "deserialize_base64")]
     pub reauth_token_public_key: Arc<Vec<u8>>,
 
+    #[serde(default)]
+    pub access_token_format: TokenFormat,
+
+    #[serde(default)]
+    pub refresh_token_format: TokenFormat,
+
+    #[serde(default)]
+    pub session_token_format: TokenFormat,
+
+    #[serde(default)]
+    pub reauth_token_format: TokenFormat,
+
+    #[serde(default, deserialize_with = "deserialize_optional_base64")]
+    pub paseto_public_key: Option<Arc<Vec<u8>>>,
+
+    #[serde(default, deserialize_with = "deserialize_optional_base64")]
+    pub paseto_private_key: Option<Arc<Vec<u8>>>,
+
+    #[serde(default, deserialize_with = "deserialize_optional_base64")]
+    pub session_token_encryption_public_key: Option<Arc<Vec<u8>>>,
+
+    #[serde(default, deserialize_with = "deserialize_optional_base64")]
+    pub session_token_encryption_private_key: Option<Arc<Vec<u8>>>,
+
     #[validate(range(
         min = TryInto::<usize>::try_into(Duration::days(15).whole_seconds()).unwrap(),
         max = TryInto::<usize>::try_into(Duration::days(90).whole_seconds()).unwrap(),
@@ -91,6 +281,27 @@ pub struct Env {
     ))]
     pub refresh_token_expiration: usize,
 
+    #[validate(range(
+        min = TryInto::<usize>::try_into(Duration::hours(1).whole_seconds()).unwrap(),
+        max = TryInto::<usize>::try_into(Duration::days(1).whole_seconds()).unwrap(),
+        message = "SHORT_REFRESH_TOKEN_EXPIRATION must be between 1 hour and 1 day"
+    ))]
+    pub short_refresh_token_expiration: usize,
+
+    #[validate(range(
+        max = TryInto::<usize>::try_into(Duration::days(30).whole_seconds()).unwrap(),
+        message = "SESSION_IDLE_TIMEOUT must be at most 30 days"
+    ))]
+    #[serde(default)]
+    pub session_idle_timeout: usize,
+
+    #[validate(range(
+        min = TryInto::<usize>::try_into(Duration::days(15).whole_seconds()).unwrap(),
+        max = TryInto::<usize>::try_into(Duration::days(365).whole_seconds()).unwrap(),
+        message = "SESSION_ABSOLUTE_LIFETIME must be between 15 days and 1 year"
+    ))]
+    pub session_absolute_lifetime: usize,
+
     #[validate(range(
         min = TryInto::<usize>::try_into(Duration::days(15).whole_seconds()).unwrap(),
         max = TryInto::<usize>::try_into(Duration::days(90).whole_seconds()).unwrap(),
@@ -112,6 +323,27 @@ pub struct Env {
     ))]
     pub reauth_token_expiration: usize,
 
+    #[validate(range(
+        min = TryInto::<usize>::try_into(Duration::minutes(1).whole_seconds()).unwrap(),
+        max = TryInto::<usize>::try_into(Duration::minutes(15).whole_seconds()).unwrap(),
+        message = "EXCHANGE_TOKEN_EXPIRATION must be between 1 minute and 15 minutes"
+    ))]
+    pub exchange_token_expiration: usize,
+
+    #[validate(range(
+        min = TryInto::<usize>::try_into(Duration::minutes(5).whole_seconds()).unwrap(),
+        max = TryInto::<usize>::try_into(Duration::minutes(30).whole_seconds()).unwrap(),
+        message = "MAGIC_LINK_EXPIRATION must be between 5 minutes and 30 minutes"
+    ))]
+    pub magic_link_expiration: usize,
+
+    #[validate(range(
+        min = TryInto::<usize>::try_into(Duration::minutes(5).whole_seconds()).unwrap(),
+        max = TryInto::<usize>::try_into(Duration::hours(1).whole_seconds()).unwrap(),
+        message = "PASSWORD_RESET_EXPIRATION must be between 5 minutes and 1 hour"
+    ))]
+    pub password_reset_expiration: usize,
+
     #[validate(range(
         min = 50050,
         max = 50060,
@@ -120,6 +352,67 @@ pub struct Env {
     pub port: u16,
 }
 
+fn default_geoip_cache_ttl() -> usize {
+    Duration::days(1).whole_seconds().try_into().unwrap()
+}
+
+fn default_geoip_breaker_threshold() -> usize {
+    5
+}
+
+fn default_geoip_breaker_cooldown() -> u64 {
+    30
+}
+
+fn default_job_workers() -> usize {
+    4
+}
+
+fn default_job_max_attempts() -> u32 {
+    8
+}
+
+fn default_sweeper_interval() -> u64 {
+    10 * 60
+}
+
+fn validate_geoip(env: &Env) -> Result<(), ValidationError> {
+    match env.geoip_provider {
+        GeoIpProvider::Ipinfo if env.ipinfo_api_key.is_empty() => {
+            Err(ValidationError::new("geoip")
+                .with_message("IPINFO_API_KEY is required when GEOIP_PROVIDER is ipinfo".into()))
+        }
+        GeoIpProvider::Mmdb if env.geoip_city_database.is_none() => {
+            Err(ValidationError::new("geoip")
+                .with_message("GEOIP_CITY_DATABASE is required when GEOIP_PROVIDER is mmdb".into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn validate_exchange_targets(env: &Env) -> Result<(), ValidationError> {
+    let registered = |audience: &String| env.token_audiences.contains(audience);
+    for (source, targets) in &env.token_exchange_targets {
+        if !registered(source) || !targets.iter().all(registered) {
+            return Err(ValidationError::new("exchange_targets").with_message(
+                "TOKEN_EXCHANGE_TARGETS must only contain audiences from TOKEN_AUDIENCES".into(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_session_lifetime(env: &Env) -> Result<(), ValidationError> {
+    if env.session_absolute_lifetime < env.refresh_token_expiration {
+        return Err(ValidationError::new("session_lifetime").with_message(
+            "SESSION_ABSOLUTE_LIFETIME must not be shorter than REFRESH_TOKEN_EXPIRATION".into(),
+        ));
+    }
+
+    Ok(())
+}
+
 impl Default for Env {
     fn default() -> Self {
         Self::new()
@@ -130,7 +423,7 @@ impl Env {
     pub fn new() -> Self {
         let _ = dotenv();
 
-        let env: Self = envy::from_env().unwrap_or_else(|e| {
+        let mut env: Self = envy::from_env().unwrap_or_else(|e| {
             eprintln!("{}, exiting ... ", e);
             exit(1);
         });
@@ -139,10 +432,21 @@ impl Env {
             eprintln!("update the environment and try again, exiting ... ");
             exit(1);
         });
+        let keys = Keys::new(&env).unwrap_or_else(|e| {
+            eprintln!("failed to load token keys: {:#}, exiting ... ", e);
+            exit(1);
+        });
+        env.keys = Some(Arc::new(keys));
 
         env_logger::init();
         env
     }
+
+    pub fn keys(&self) -> Arc<Keys> {
+        self.keys
+            .clone()
+            .expect("token keys are parsed in Env::new")
+    }
 }
 
 pub static ENV: Lazy<Env> = Lazy::new(Env::new);
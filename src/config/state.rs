@@ -1,15 +1,26 @@
-use super::ENV;
+use super::{
+    ENV,
+    env::{GeoIpProvider, TokenStoreKind},
+};
+use crate::geo::{CachedGeoIp, CircuitBreaker, GeoIp, IpInfoGeoIp, MmdbGeoIp, NullGeoIp};
+use crate::token::{
+    keys::Keys,
+    store::{MemoryStore, RedisStore, TokenStore},
+};
 use envmode::EnvMode;
 use redis::{Client as RedisClient, RedisError, aio::MultiplexedConnection};
 use resend_rs::Resend;
 use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
-use std::{process::exit, time::Duration};
+use std::{process::exit, sync::Arc, time::Duration};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: DatabaseConnection,
     pub rd: RedisClient,
     pub resend: Resend,
+    pub store: Arc<dyn TokenStore>,
+    pub keys: Arc<Keys>,
+    pub geoip: Arc<dyn GeoIp>,
 }
 
 impl AppState {
@@ -26,6 +37,8 @@ impl AppState {
 
 impl AppState {
     pub async fn new() -> Self {
+        let keys = ENV.keys();
+
         let mut opt = ConnectOptions::new(&*ENV.database_url);
         opt.max_lifetime(Duration::from_secs(8))
             .idle_timeout(Duration::from_secs(5))
@@ -46,8 +59,45 @@ impl AppState {
             exit(1);
         });
         let resend = Resend::new(&ENV.resend_api);
+        let geoip: Arc<dyn GeoIp> = match ENV.geoip_provider {
+            GeoIpProvider::Ipinfo => {
+                // the breaker sits below the cache so cached ips resolve even
+                // while ipinfo is failing
+                let ipinfo: Arc<dyn GeoIp> = Arc::new(CircuitBreaker::new(
+                    Arc::new(IpInfoGeoIp::new(ENV.ipinfo_api_key.clone())),
+                    ENV.geoip_breaker_threshold,
+                    Duration::from_secs(ENV.geoip_breaker_cooldown),
+                ));
+                match ENV.geoip_cache_ttl {
+                    0 => ipinfo,
+                    ttl => Arc::new(CachedGeoIp::new(ipinfo, rd.clone(), ttl)),
+                }
+            }
+            GeoIpProvider::Mmdb => Arc::new(
+                MmdbGeoIp::open(
+                    ENV.geoip_city_database.as_deref().unwrap_or_default(),
+                    ENV.geoip_asn_database.as_deref(),
+                )
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to load the geoip database: {:#}", e);
+                    exit(1);
+                }),
+            ),
+            GeoIpProvider::None => Arc::new(NullGeoIp),
+        };
+        let store: Arc<dyn TokenStore> = match ENV.token_store {
+            TokenStoreKind::Redis => Arc::new(RedisStore::new(rd.clone())),
+            TokenStoreKind::Memory => Arc::new(MemoryStore::default()),
+        };
 
-        Self { db, rd, resend }
+        Self {
+            db,
+            rd,
+            resend,
+            store,
+            keys,
+            geoip,
+        }
     }
 }
 
@@ -0,0 +1,35 @@
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait, QueryFilter, entity::*};
+
+pub async fn find_for_credential(
+    db: &DatabaseConnection,
+    credential: &str,
+) -> Result<Option<entity::directory::Model>, DbErr> {
+    let credential = credential.to_lowercase();
+    if let Some((_, domain)) = credential.rsplit_once('@') {
+        return entity::directory::Entity::find()
+            .filter(entity::directory::Column::Domain.eq(domain))
+            .one(db)
+            .await;
+    }
+
+    let user = entity::user::Entity::find()
+        .filter(entity::user::Column::Username.eq(&credential))
+        .one(db)
+        .await?;
+    let Some(user) = user else {
+        return Ok(None);
+    };
+
+    let providers: Vec<String> = entity::user_provider::Entity::find()
+        .filter(entity::user_provider::Column::UserId.eq(user.id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|user_provider| user_provider.provider_id)
+        .collect();
+
+    entity::directory::Entity::find()
+        .filter(entity::directory::Column::ProviderId.is_in(providers))
+        .one(db)
+        .await
+}
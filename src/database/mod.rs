@@ -1,4 +1,7 @@
 pub mod admin;
 pub mod api_key;
+pub mod directory;
+pub mod risk;
+pub mod role;
 pub mod session;
 pub mod user;
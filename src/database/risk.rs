@@ -0,0 +1,32 @@
+use super::session::to_decimal;
+use crate::{geo::Location, risk::Assessment, util::now};
+use sea_orm::{ActiveModelTrait, DatabaseConnection, DbErr, Set};
+
+pub async fn create(
+    db: &DatabaseConnection,
+    user_id: &str,
+    location: &Location,
+    assessment: &Assessment,
+) -> Result<(), DbErr> {
+    let risk = entity::login_risk::ActiveModel {
+        user_id: Set(user_id.to_owned()),
+        ip_address: Set(location.ip_address.clone()),
+        country: Set(location.country.clone()),
+        asn: Set(location.asn.clone()),
+        lat: Set(to_decimal(location.lat)?),
+        lon: Set(to_decimal(location.lon)?),
+        score: Set(assessment.score),
+        signals: Set(assessment
+            .signals
+            .iter()
+            .map(|signal| signal.to_string())
+            .collect::<Vec<_>>()
+            .join(",")),
+        decision: Set(assessment.decision.to_string()),
+        created_at: Set(now().try_into().unwrap()),
+        ..Default::default()
+    };
+    let _ = risk.insert(db).await?;
+
+    Ok(())
+}
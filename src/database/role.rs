@@ -0,0 +1,151 @@
+use crate::model::role::Grants;
+use sea_orm::{
+    ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, QuerySelect,
+    Set, TransactionTrait, entity::*,
+};
+use std::collections::{BTreeSet, HashMap};
+
+pub async fn create(
+    db: &DatabaseConnection,
+    name: &str,
+    description: &str,
+    permissions: &[String],
+) -> Result<entity::role::Model, DbErr> {
+    let txn = db.begin().await?;
+
+    let role = entity::role::ActiveModel {
+        name: Set(name.to_lowercase()),
+        description: Set(description.to_owned()),
+        ..Default::default()
+    };
+    let role = role.insert(&txn).await?;
+
+    for name in permissions.iter().collect::<BTreeSet<_>>() {
+        let permission = entity::permission::Entity::find()
+            .filter(entity::permission::Column::Name.eq(name.as_str()))
+            .one(&txn)
+            .await?;
+        let permission = match permission {
+            Some(permission) => permission,
+            None => {
+                entity::permission::ActiveModel {
+                    name: Set(name.to_owned()),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?
+            }
+        };
+
+        let role_permission = entity::role_permission::ActiveModel {
+            role_id: Set(role.id.clone()),
+            permission_id: Set(permission.id),
+        };
+        let _ = role_permission.insert(&txn).await?;
+    }
+
+    txn.commit().await?;
+    Ok(role)
+}
+
+pub async fn get_by_name(
+    db: &DatabaseConnection,
+    name: &str,
+) -> Result<entity::role::Model, DbErr> {
+    let role = entity::role::Entity::find()
+        .filter(entity::role::Column::Name.eq(name.to_lowercase()))
+        .one(db)
+        .await?;
+    let role = role.ok_or(DbErr::RecordNotFound(String::from(
+        "role with the given name does not exist",
+    )))?;
+
+    Ok(role)
+}
+
+pub async fn delete(db: &DatabaseConnection, name: &str) -> Result<(), DbErr> {
+    let role = get_by_name(db, name).await?;
+    role.delete(db).await?;
+
+    Ok(())
+}
+
+pub async fn assign(db: &DatabaseConnection, user_id: &str, name: &str) -> Result<(), DbErr> {
+    let role = get_by_name(db, name).await?;
+
+    let user_role = entity::user_role::ActiveModel {
+        user_id: Set(user_id.to_owned()),
+        role_id: Set(role.id),
+        ..Default::default()
+    };
+    let _ = user_role.insert(db).await?;
+
+    Ok(())
+}
+
+pub async fn revoke(db: &DatabaseConnection, user_id: &str, name: &str) -> Result<(), DbErr> {
+    let role = get_by_name(db, name).await?;
+
+    let result = entity::user_role::Entity::delete_by_id((user_id.to_owned(), role.id))
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(DbErr::RecordNotFound(String::from(
+            "user does not have the given role",
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn get_grants(db: &DatabaseConnection, user_id: &str) -> Result<Grants, DbErr> {
+    let roles = entity::role::Entity::find()
+        .inner_join(entity::user_role::Entity)
+        .filter(entity::user_role::Column::UserId.eq(user_id))
+        .order_by_asc(entity::role::Column::Name)
+        .all(db)
+        .await?;
+    if roles.is_empty() {
+        return Ok(Grants::default());
+    }
+
+    let permissions = entity::permission::Entity::find()
+        .inner_join(entity::role_permission::Entity)
+        .filter(
+            entity::role_permission::Column::RoleId.is_in(roles.iter().map(|role| role.id.clone())),
+        )
+        .order_by_asc(entity::permission::Column::Name)
+        .distinct()
+        .all(db)
+        .await?;
+
+    Ok(Grants {
+        roles: roles.into_iter().map(|role| role.name).collect(),
+        permissions: permissions
+            .into_iter()
+            .map(|permission| permission.name)
+            .collect(),
+    })
+}
+
+pub async fn get_permissions(
+    db: &DatabaseConnection,
+    names: &[String],
+) -> Result<HashMap<String, Vec<String>>, DbErr> {
+    let roles = entity::role::Entity::find()
+        .filter(entity::role::Column::Name.is_in(names.iter().map(|name| name.to_lowercase())))
+        .find_with_related(entity::permission::Entity)
+        .all(db)
+        .await?;
+
+    Ok(roles
+        .into_iter()
+        .map(|(role, permissions)| {
+            let permissions = permissions
+                .into_iter()
+                .map(|permission| permission.name)
+                .collect();
+            (role.name, permissions)
+        })
+        .collect())
+}
@@ -1,11 +1,12 @@
-use crate::{config::ENV, util::now};
-use ipinfo::{IpInfo, IpInfoConfig};
+use crate::{
+    geo::Location,
+    util::{now, user_agent},
+};
 use prelude::Decimal;
 use sea_orm::{
-    ActiveModelTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
-    entity::*,
+    ActiveModelTrait, Condition, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, entity::*, sea_query::Expr,
 };
-use woothee::parser::Parser;
 
 pub struct Device {
     pub vendor: String,
@@ -22,108 +23,209 @@ pub struct Browser {
     pub version: String,
 }
 
+pub fn to_decimal(value: Option<f32>) -> Result<Option<Decimal>, DbErr> {
+    value
+        .map(|value| {
+            Decimal::from_f32_retain(value)
+                .ok_or_else(|| DbErr::Custom(String::from("failed to convert to decimal")))
+        })
+        .transpose()
+}
+
 pub async fn create(
     db: &DatabaseConnection,
     id: &str,
     user_id: &str,
-    ip_address: &str,
     user_agent: &str,
-) -> Result<(), DbErr> {
+    exp: usize,
+    remember_me: bool,
+    location: Location,
+) -> Result<entity::session::Model, DbErr> {
     let now = now();
-    let exp: i32 = (now + ENV.refresh_token_expiration)
+    let exp: i32 = exp
         .try_into()
         .map_err(|_| DbErr::Custom(String::from("failed to convert expiration to i32")))?;
 
-    let mut lat: Option<f32> = None;
-    let mut lon: Option<f32> = None;
-    let mut country: Option<String> = None;
-    let mut city: Option<String> = None;
-    let mut region: Option<String> = None;
-    let mut timezone: Option<String> = None;
-    let mut map_url: Option<String> = None;
-
-    let mut device_vendor: Option<String> = None;
-    let mut device_model: Option<String> = None;
-    let mut os_name: Option<String> = None;
-    let mut os_version: Option<String> = None;
-    let mut browser_name: Option<String> = None;
-    let mut browser_version: Option<String> = None;
-
-    let parser = Parser::new();
-    let result = parser.parse(user_agent);
-
-    if let Some(result) = result {
-        browser_name = Some(result.name.to_owned());
-        browser_version = Some(result.version.to_owned());
-        os_name = Some(result.os.to_owned());
-        os_version = Some(result.os_version.to_string());
-        device_vendor = Some(result.category.to_owned());
-        device_model = Some(result.vendor.to_owned());
-    }
-
-    if ip_address != "127.0.0.1" && !ip_address.is_empty() {
-        let config = IpInfoConfig {
-            token: Some((*ENV.ipinfo_api_key).to_owned()),
-            ..Default::default()
-        };
-
-        let mut ipinfo = IpInfo::new(config).map_err(|err| DbErr::Custom(err.to_string()))?;
-        let result = ipinfo
-            .lookup(ip_address)
-            .await
-            .map_err(|err| DbErr::Custom(err.to_string()))?;
-
-        let mut split = result.loc.split(',');
-
-        lat = split.next().and_then(|lat| lat.parse().ok()).or(Some(0.0));
-        lon = split.next().and_then(|lon| lon.parse().ok()).or(Some(0.0));
-
-        country = Some(result.country);
-        city = Some(result.city);
-        region = Some(result.region);
-        timezone = result.timezone;
-
-        map_url = Some(format!(
-            "https://www.openstreetmap.org/?mlat={}&mlon={}",
-            lat.unwrap_or(0.0),
-            lon.unwrap_or(0.0),
-        ));
-    }
-
-    let lat = lat
-        .map(|lat| {
-            Decimal::from_f32_retain(lat)
-                .ok_or_else(|| DbErr::Custom(String::from("failed to convert lat to decimal")))
-        })
-        .transpose()?;
-    let lon = lon
-        .map(|lon| {
-            Decimal::from_f32_retain(lon)
-                .ok_or_else(|| DbErr::Custom(String::from("failed to convert lon to decimal")))
-        })
-        .transpose()?;
+    let user_agent = user_agent::parse(user_agent);
 
     let session = entity::session::ActiveModel {
         id: Set(id.to_owned()),
         user_id: Set(user_id.to_owned()),
         exp: Set(exp),
         login_at: Set(now.try_into().unwrap()),
-        lat: Set(lat),
-        lon: Set(lon),
-        ip_address: Set(ip_address.to_owned()),
-        device_vendor: Set(device_vendor),
-        device_model: Set(device_model),
-        os_name: Set(os_name),
-        os_version: Set(os_version),
-        browser_name: Set(browser_name),
-        borwser_version: Set(browser_version),
-        country: Set(country),
-        city: Set(city),
-        region: Set(region),
-        timezone: Set(timezone),
-        map_url: Set(map_url),
+        lat: Set(to_decimal(location.lat)?),
+        lon: Set(to_decimal(location.lon)?),
+        ip_address: Set(location.ip_address.clone()),
+        device_vendor: Set(user_agent.device_vendor),
+        device_model: Set(user_agent.device_model),
+        os_name: Set(user_agent.os_name),
+        os_version: Set(user_agent.os_version),
+        browser_name: Set(user_agent.browser_name),
+        borwser_version: Set(user_agent.browser_version),
+        country: Set(location.country),
+        city: Set(location.city),
+        region: Set(location.region),
+        timezone: Set(location.timezone),
+        map_url: Set(location.map_url),
+        remember_me: Set(remember_me),
+        last_seen_at: Set(now.try_into().unwrap()),
+        last_ip_address: Set(Some(location.ip_address.clone())),
+        refresh_count: Set(0),
+        asn: Set(location.asn),
+    };
+    session.insert(db).await
+}
+
+/// whether the session comes from a browser, os and country combination the
+/// user has no other session from, a user's very first session is never new
+pub async fn is_new_device(
+    db: &DatabaseConnection,
+    session: &entity::session::Model,
+) -> Result<bool, DbErr> {
+    let others = Condition::all()
+        .add(entity::session::Column::UserId.eq(&session.user_id))
+        .add(entity::session::Column::Id.ne(&session.id));
+    let previous = entity::session::Entity::find()
+        .filter(others.clone())
+        .count(db)
+        .await?;
+    if previous == 0 {
+        return Ok(false);
+    }
+
+    let matches = |column: entity::session::Column, value: &Option<String>| match value {
+        Some(value) => column.eq(value),
+        None => column.is_null(),
     };
-    let _: entity::session::Model = session.insert(db).await?;
+    let known = entity::session::Entity::find()
+        .filter(
+            others
+                .add(matches(
+                    entity::session::Column::BrowserName,
+                    &session.browser_name,
+                ))
+                .add(matches(entity::session::Column::OsName, &session.os_name))
+                .add(matches(entity::session::Column::Country, &session.country)),
+        )
+        .count(db)
+        .await?;
+
+    Ok(known == 0)
+}
+
+pub async fn get_by_id(
+    db: &DatabaseConnection,
+    rjti: &str,
+) -> Result<entity::session::Model, DbErr> {
+    let session = entity::session::Entity::find_by_id(rjti).one(db).await?;
+    let session = session.ok_or(DbErr::RecordNotFound(String::from(
+        "session with the given id does not exist",
+    )))?;
+
+    Ok(session)
+}
+
+/// a session owned by the given user, sessions of other users are reported as
+/// missing so their ids cannot be probed
+pub async fn get_by_user(
+    db: &DatabaseConnection,
+    rjti: &str,
+    user_id: &str,
+) -> Result<entity::session::Model, DbErr> {
+    let session = entity::session::Entity::find_by_id(rjti)
+        .filter(entity::session::Column::UserId.eq(user_id))
+        .one(db)
+        .await?;
+    let session = session.ok_or(DbErr::RecordNotFound(String::from(
+        "session with the given id does not exist",
+    )))?;
+
+    Ok(session)
+}
+
+/// the most recently active sessions of a user
+pub async fn recent(
+    db: &DatabaseConnection,
+    user_id: &str,
+    limit: u64,
+) -> Result<Vec<entity::session::Model>, DbErr> {
+    entity::session::Entity::find()
+        .filter(entity::session::Column::UserId.eq(user_id))
+        .order_by_desc(entity::session::Column::LastSeenAt)
+        .limit(limit)
+        .all(db)
+        .await
+}
+
+/// active sessions of a user, most recent login first, along with the total
+/// number of active sessions
+pub async fn list(
+    db: &DatabaseConnection,
+    user_id: &str,
+    live: &[String],
+    page: u64,
+    page_size: u64,
+) -> Result<(Vec<entity::session::Model>, u64), DbErr> {
+    let now: i64 = now().try_into().unwrap();
+
+    let paginator = entity::session::Entity::find()
+        .filter(
+            Condition::all()
+                .add(entity::session::Column::UserId.eq(user_id))
+                .add(entity::session::Column::Id.is_in(live.iter().cloned()))
+                .add(entity::session::Column::Exp.gt(now)),
+        )
+        .order_by_desc(entity::session::Column::LoginAt)
+        .order_by_desc(entity::session::Column::Id)
+        .paginate(db, page_size);
+    let total = paginator.num_items().await?;
+    let sessions = paginator.fetch_page(page.saturating_sub(1)).await?;
+
+    Ok((sessions, total))
+}
+
+pub async fn set_exp(db: &DatabaseConnection, rjti: &str, exp: usize) -> Result<(), DbErr> {
+    let exp: i32 = exp
+        .try_into()
+        .map_err(|_| DbErr::Custom(String::from("failed to convert expiration to i32")))?;
+
+    entity::session::Entity::update_many()
+        .col_expr(entity::session::Column::Exp, Expr::value(exp))
+        .filter(entity::session::Column::Id.eq(rjti))
+        .exec(db)
+        .await?;
+
+    Ok(())
+}
+
+/// records activity on a session, `refreshes` is the number of refreshes since
+/// the last recorded activity
+pub async fn touch(
+    db: &DatabaseConnection,
+    rjti: &str,
+    ip_address: Option<&str>,
+    refreshes: i32,
+) -> Result<(), DbErr> {
+    let now: i32 = now()
+        .try_into()
+        .map_err(|_| DbErr::Custom(String::from("failed to convert last seen to i32")))?;
+
+    let mut update = entity::session::Entity::update_many()
+        .col_expr(entity::session::Column::LastSeenAt, Expr::value(now))
+        .col_expr(
+            entity::session::Column::RefreshCount,
+            Expr::col(entity::session::Column::RefreshCount).add(refreshes),
+        );
+    if let Some(ip_address) = ip_address {
+        update = update.col_expr(
+            entity::session::Column::LastIpAddress,
+            Expr::value(ip_address),
+        );
+    }
+    update
+        .filter(entity::session::Column::Id.eq(rjti))
+        .exec(db)
+        .await?;
 
     Ok(())
 }
@@ -151,3 +253,81 @@ pub async fn delete_expired_user_sessions(
 
     Ok(())
 }
+
+/// deletes up to `limit` expired sessions across all users, returns how many
+/// were deleted
+pub async fn delete_expired(db: &DatabaseConnection, limit: u64) -> Result<u64, DbErr> {
+    let now: i64 = now().try_into().unwrap();
+
+    let ids: Vec<String> = entity::session::Entity::find()
+        .select_only()
+        .column(entity::session::Column::Id)
+        .filter(entity::session::Column::Exp.lte(now))
+        .limit(limit)
+        .into_tuple()
+        .all(db)
+        .await?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let result = entity::session::Entity::delete_many()
+        .filter(entity::session::Column::Id.is_in(ids))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
+
+/// ids of active sessions after `after` in id order, for walking the table in
+/// batches
+pub async fn active_ids(
+    db: &DatabaseConnection,
+    after: Option<&str>,
+    limit: u64,
+) -> Result<Vec<String>, DbErr> {
+    let now: i64 = now().try_into().unwrap();
+
+    let mut condition = Condition::all().add(entity::session::Column::Exp.gt(now));
+    if let Some(after) = after {
+        condition = condition.add(entity::session::Column::Id.gt(after));
+    }
+
+    entity::session::Entity::find()
+        .select_only()
+        .column(entity::session::Column::Id)
+        .filter(condition)
+        .order_by_asc(entity::session::Column::Id)
+        .limit(limit)
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+/// the subset of `ids` that have a session record
+pub async fn existing_ids(db: &DatabaseConnection, ids: &[String]) -> Result<Vec<String>, DbErr> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    entity::session::Entity::find()
+        .select_only()
+        .column(entity::session::Column::Id)
+        .filter(entity::session::Column::Id.is_in(ids.iter().cloned()))
+        .into_tuple()
+        .all(db)
+        .await
+}
+
+pub async fn delete_many(db: &DatabaseConnection, ids: &[String]) -> Result<u64, DbErr> {
+    if ids.is_empty() {
+        return Ok(0);
+    }
+
+    let result = entity::session::Entity::delete_many()
+        .filter(entity::session::Column::Id.is_in(ids.iter().cloned()))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
@@ -1,6 +1,6 @@
 use sea_orm::{
-    ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set, TransactionTrait,
-    entity::*,
+    ActiveModelTrait, DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter, Set,
+    TransactionTrait, entity::*, sea_query::Expr,
 };
 
 pub async fn create(
@@ -64,6 +64,15 @@ pub async fn get_by_email(
     Ok(user)
 }
 
+pub async fn get_by_id(db: &DatabaseConnection, id: &str) -> Result<entity::user::Model, DbErr> {
+    let user = entity::user::Entity::find_by_id(id).one(db).await?;
+    let user = user.ok_or(DbErr::RecordNotFound(String::from(
+        "user with the given id does not exist",
+    )))?;
+
+    Ok(user)
+}
+
 pub async fn get_by_credential(
     db: &DatabaseConnection,
     credential: &str,
@@ -83,3 +92,109 @@ pub async fn get_by_credential(
 
     Ok(user)
 }
+
+pub async fn has_provider(
+    db: &DatabaseConnection,
+    id: &str,
+    provider_id: &str,
+) -> Result<bool, DbErr> {
+    let count = entity::user_provider::Entity::find()
+        .filter(entity::user_provider::Column::UserId.eq(id))
+        .filter(entity::user_provider::Column::ProviderId.eq(provider_id))
+        .count(db)
+        .await?;
+
+    Ok(count > 0)
+}
+
+pub async fn get_tokens_valid_after(db: &DatabaseConnection, id: &str) -> Result<i64, DbErr> {
+    let user = get_by_id(db, id).await?;
+
+    Ok(user.tokens_valid_after)
+}
+
+pub async fn set_tokens_valid_after(
+    db: &DatabaseConnection,
+    id: &str,
+    epoch: i64,
+) -> Result<(), DbErr> {
+    let result = entity::user::Entity::update_many()
+        .col_expr(entity::user::Column::TokensValidAfter, Expr::value(epoch))
+        .filter(entity::user::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(DbErr::RecordNotFound(String::from(
+            "user with the given id does not exist",
+        )));
+    }
+
+    Ok(())
+}
+
+/// replaces the password and lifts a pending reset requirement
+pub async fn reset_password(
+    db: &DatabaseConnection,
+    id: &str,
+    password: &str,
+) -> Result<(), DbErr> {
+    let password = bcrypt::hash(password, bcrypt::DEFAULT_COST)
+        .map_err(|err| DbErr::Custom(err.to_string()))?;
+    let result = entity::user::Entity::update_many()
+        .col_expr(entity::user::Column::Password, Expr::value(password))
+        .col_expr(
+            entity::user::Column::PasswordResetRequired,
+            Expr::value(false),
+        )
+        .filter(entity::user::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(DbErr::RecordNotFound(String::from(
+            "user with the given id does not exist",
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn set_password_reset_required(
+    db: &DatabaseConnection,
+    id: &str,
+    required: bool,
+) -> Result<(), DbErr> {
+    let result = entity::user::Entity::update_many()
+        .col_expr(
+            entity::user::Column::PasswordResetRequired,
+            Expr::value(required),
+        )
+        .filter(entity::user::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(DbErr::RecordNotFound(String::from(
+            "user with the given id does not exist",
+        )));
+    }
+
+    Ok(())
+}
+
+pub async fn set_max_sessions(
+    db: &DatabaseConnection,
+    id: &str,
+    max_sessions: Option<i32>,
+) -> Result<(), DbErr> {
+    let result = entity::user::Entity::update_many()
+        .col_expr(entity::user::Column::MaxSessions, Expr::value(max_sessions))
+        .filter(entity::user::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(DbErr::RecordNotFound(String::from(
+            "user with the given id does not exist",
+        )));
+    }
+
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use sea_orm::{DbErr, RuntimeErr};
+use sea_orm::{DbErr, SqlErr};
 use std::fmt::{Display, Formatter, Result};
 use tonic::{Code, Status};
 use validator::ValidationErrors;
@@ -98,26 +98,16 @@ impl AppError {
             | TokenError::InvalidFormat(source)
             | TokenError::Parsing(source)
             | TokenError::Validation(source) => Self::BadRequest(anyhow::anyhow!(source)),
+            TokenError::SessionLimit(source) => Self::Unauthorized(source),
             _ => Self::Other(error.into()),
         }
     }
 }
 
+/// inserts report unique violations as query or exec errors depending on the
+/// statement, the sql error covers both
 fn is_unique_violation(err: &DbErr) -> bool {
-    match err {
-        DbErr::Query(RuntimeErr::SqlxError(error)) => {
-            if let Some(db_error) = error.as_database_error() {
-                if let Some(code) = db_error.code() {
-                    code == "23505"
-                } else {
-                    false
-                }
-            } else {
-                false
-            }
-        }
-        _ => false,
-    }
+    matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_)))
 }
 
 impl From<AppError> for Status {
@@ -0,0 +1,89 @@
+use super::{GeoIp, Location};
+use crate::metrics;
+use std::{
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+#[derive(Default)]
+struct State {
+    failures: usize,
+    open_until: Option<Instant>,
+}
+
+/// stops calling a failing backend, after `threshold` consecutive failures
+/// lookups fail immediately for `cooldown`, then a single lookup is let
+/// through to probe whether the backend recovered
+pub struct CircuitBreaker {
+    inner: Arc<dyn GeoIp>,
+    threshold: usize,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(inner: Arc<dyn GeoIp>, threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            inner,
+            threshold,
+            cooldown,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// whether a lookup may go through, a lapsed cooldown re-arms the breaker
+    /// so only the probing lookup passes until it reports back
+    fn allow(&self) -> bool {
+        let mut state = self.state();
+        match state.open_until {
+            Some(until) if until > Instant::now() => false,
+            Some(_) => {
+                state.open_until = Some(Instant::now() + self.cooldown);
+                true
+            }
+            None => true,
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut state = self.state();
+        if success {
+            *state = State::default();
+            return;
+        }
+
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            if state.open_until.is_none() {
+                log::warn!(
+                    "geoip backend failed {} times in a row, pausing lookups for {:?}",
+                    state.failures,
+                    self.cooldown
+                );
+            }
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl GeoIp for CircuitBreaker {
+    async fn lookup(&self, ip: IpAddr) -> Result<Location, anyhow::Error> {
+        if !self.allow() {
+            metrics::GEOIP_BREAKER_REJECTIONS.inc();
+            return Err(anyhow::anyhow!("geoip circuit breaker is open"));
+        }
+
+        let result = self.inner.lookup(ip).await;
+        self.record(result.is_ok());
+
+        result
+    }
+}
@@ -0,0 +1,61 @@
+use super::{GeoIp, Location};
+use crate::{config::ENV, metrics};
+use redis::Client;
+use std::{net::IpAddr, sync::Arc};
+
+/// caches the locations resolved by another backend in redis, keyed by ip
+pub struct CachedGeoIp {
+    inner: Arc<dyn GeoIp>,
+    rd: Client,
+    ttl: usize,
+}
+
+impl CachedGeoIp {
+    pub fn new(inner: Arc<dyn GeoIp>, rd: Client, ttl: usize) -> Self {
+        Self { inner, rd, ttl }
+    }
+}
+
+fn key(ip: IpAddr) -> String {
+    format!("{}:geoip:{}", &*ENV.redis_schema, ip)
+}
+
+#[tonic::async_trait]
+impl GeoIp for CachedGeoIp {
+    async fn lookup(&self, ip: IpAddr) -> Result<Location, anyhow::Error> {
+        // the cache is best effort, an unavailable redis falls through to the
+        // backend instead of failing the lookup
+        let mut conn = self.rd.get_multiplexed_async_connection().await.ok();
+        if let Some(conn) = conn.as_mut() {
+            let cached: Option<String> = redis::cmd("GET")
+                .arg(key(ip))
+                .query_async(conn)
+                .await
+                .unwrap_or_default();
+            if let Some(location) = cached.and_then(|value| serde_json::from_str(&value).ok()) {
+                metrics::GEOIP_CACHE_HITS.inc();
+                return Ok(location);
+            }
+        }
+        metrics::GEOIP_CACHE_MISSES.inc();
+
+        let location = self.inner.lookup(ip).await?;
+        if let (Some(conn), Ok(value)) = (conn.as_mut(), serde_json::to_string(&location)) {
+            let cached: Result<(), _> = redis::cmd("SET")
+                .arg(key(ip))
+                .arg(value)
+                .arg("EX")
+                .arg(self.ttl)
+                .query_async(conn)
+                .await;
+            if let Err(err) = cached {
+                log::warn!(
+                    "{:#}",
+                    anyhow::Error::new(err).context("failed to cache the geoip location")
+                );
+            }
+        }
+
+        Ok(location)
+    }
+}
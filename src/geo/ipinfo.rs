@@ -0,0 +1,50 @@
+use super::{GeoIp, Location};
+use ipinfo::{IpInfo, IpInfoConfig};
+use std::{net::IpAddr, sync::Arc};
+
+/// resolves addresses with the ipinfo.io api
+pub struct IpInfoGeoIp {
+    token: Arc<str>,
+}
+
+impl IpInfoGeoIp {
+    pub fn new(token: Arc<str>) -> Self {
+        Self { token }
+    }
+}
+
+#[tonic::async_trait]
+impl GeoIp for IpInfoGeoIp {
+    async fn lookup(&self, ip: IpAddr) -> Result<Location, anyhow::Error> {
+        let config = IpInfoConfig {
+            token: Some((*self.token).to_owned()),
+            ..Default::default()
+        };
+
+        let mut ipinfo = IpInfo::new(config)?;
+        let result = ipinfo.lookup(&ip.to_string()).await?;
+
+        let mut split = result.loc.split(',');
+        let lat = split.next().and_then(|lat| lat.parse().ok());
+        let lon = split.next().and_then(|lon| lon.parse().ok());
+
+        // the organization is reported as "AS15169 Google LLC"
+        let asn = result
+            .org
+            .as_deref()
+            .and_then(|org| org.split_whitespace().next())
+            .filter(|asn| asn.starts_with("AS"))
+            .map(str::to_owned);
+
+        Ok(Location {
+            lat,
+            lon,
+            country: Some(result.country),
+            city: Some(result.city),
+            region: Some(result.region),
+            timezone: result.timezone,
+            asn,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,71 @@
+use super::{GeoIp, Location};
+use anyhow::Context;
+use maxminddb::{MaxMindDBError, Reader, geoip2};
+use std::{collections::BTreeMap, net::IpAddr};
+
+/// resolves addresses from local MaxMind or DB-IP databases, the asn database
+/// is optional
+pub struct MmdbGeoIp {
+    city: Reader<Vec<u8>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+impl MmdbGeoIp {
+    pub fn open(city: &str, asn: Option<&str>) -> Result<Self, anyhow::Error> {
+        let city = Reader::open_readfile(city)
+            .with_context(|| format!("failed to open the city database at {}", city))?;
+        let asn = asn
+            .map(|asn| {
+                Reader::open_readfile(asn)
+                    .with_context(|| format!("failed to open the asn database at {}", asn))
+            })
+            .transpose()?;
+
+        Ok(Self { city, asn })
+    }
+}
+
+fn english(names: Option<BTreeMap<&str, &str>>) -> Option<String> {
+    names?.get("en").map(|name| (*name).to_owned())
+}
+
+#[tonic::async_trait]
+impl GeoIp for MmdbGeoIp {
+    async fn lookup(&self, ip: IpAddr) -> Result<Location, anyhow::Error> {
+        let city = match self.city.lookup::<geoip2::City>(ip) {
+            Ok(city) => Some(city),
+            Err(MaxMindDBError::AddressNotFoundError(_)) => None,
+            Err(err) => return Err(err.into()),
+        };
+        let asn = match &self.asn {
+            Some(reader) => match reader.lookup::<geoip2::Asn>(ip) {
+                Ok(asn) => asn.autonomous_system_number,
+                Err(MaxMindDBError::AddressNotFoundError(_)) => None,
+                Err(err) => return Err(err.into()),
+            },
+            None => None,
+        };
+
+        let mut location = Location {
+            asn: asn.map(|asn| format!("AS{}", asn)),
+            ..Default::default()
+        };
+        if let Some(city) = city {
+            let coordinates = city.location.as_ref();
+            location.lat = coordinates.and_then(|l| l.latitude).map(|lat| lat as f32);
+            location.lon = coordinates.and_then(|l| l.longitude).map(|lon| lon as f32);
+            location.timezone = coordinates.and_then(|l| l.time_zone).map(str::to_owned);
+            location.country = city
+                .country
+                .and_then(|country| country.iso_code)
+                .map(str::to_owned);
+            location.city = city.city.and_then(|city| english(city.names));
+            location.region = city
+                .subdivisions
+                .and_then(|subdivisions| subdivisions.into_iter().next())
+                .and_then(|subdivision| english(subdivision.names));
+        }
+
+        Ok(location)
+    }
+}
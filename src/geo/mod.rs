@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+pub mod breaker;
+pub mod cache;
+pub mod ipinfo;
+pub mod mmdb;
+pub mod null;
+
+pub use self::{
+    breaker::CircuitBreaker, cache::CachedGeoIp, ipinfo::IpInfoGeoIp, mmdb::MmdbGeoIp,
+    null::NullGeoIp,
+};
+
+/// where a login came from, resolved from its ip address
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Location {
+    pub ip_address: String,
+    pub lat: Option<f32>,
+    pub lon: Option<f32>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub region: Option<String>,
+    pub timezone: Option<String>,
+    pub map_url: Option<String>,
+    pub asn: Option<String>,
+}
+
+#[tonic::async_trait]
+pub trait GeoIp: Send + Sync {
+    /// resolves a public ip address, `ip_address` and `map_url` are filled in
+    /// by [`locate`]
+    async fn lookup(&self, ip: IpAddr) -> Result<Location, anyhow::Error>;
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified())
+        }
+        IpAddr::V6(ip) => !(ip.is_loopback() || ip.is_unspecified()),
+    }
+}
+
+/// resolves where a login comes from, addresses that cannot be resolved only
+/// lose their location details
+pub async fn locate(geoip: &dyn GeoIp, ip_address: &str) -> Location {
+    let empty = || Location {
+        ip_address: ip_address.to_owned(),
+        ..Default::default()
+    };
+    let Ok(ip) = ip_address.parse::<IpAddr>() else {
+        return empty();
+    };
+    if !is_public(ip) {
+        return empty();
+    }
+
+    match geoip.lookup(ip).await {
+        Ok(location) => Location {
+            ip_address: ip_address.to_owned(),
+            map_url: location.lat.zip(location.lon).map(|(lat, lon)| {
+                format!("https://www.openstreetmap.org/?mlat={}&mlon={}", lat, lon)
+            }),
+            ..location
+        },
+        Err(err) => {
+            log::warn!("{:#}", err.context("failed to resolve the login location"));
+            empty()
+        }
+    }
+}
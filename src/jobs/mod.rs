@@ -0,0 +1,177 @@
+pub mod queue;
+pub mod sweeper;
+pub mod worker;
+
+use crate::{
+    config::{ENV, state::AppState},
+    database,
+    geo::Location,
+    risk::Assessment,
+    service::auth::{notify_new_device, send_magic_link_email, send_password_reset_email},
+    token::types::refresh::Refresh,
+};
+use sea_orm::DbErr;
+use serde::{Deserialize, Serialize};
+
+pub use worker::start;
+
+/// work that has to survive a failed attempt or a restart, every job can run
+/// more than once so handlers must be idempotent
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Job {
+    CreateSession {
+        rjti: String,
+        user_id: String,
+        email: String,
+        user_agent: String,
+        exp: usize,
+        remember_me: bool,
+        location: Location,
+    },
+    NotifyNewDevice {
+        rjti: String,
+        email: String,
+    },
+    PruneSessions {
+        user_id: String,
+    },
+    SendMagicLink {
+        email: String,
+        nonce: String,
+    },
+    SendPasswordReset {
+        email: String,
+    },
+    RecordLoginRisk {
+        user_id: String,
+        location: Location,
+        assessment: Assessment,
+    },
+}
+
+impl Job {
+    /// runs the job and returns the jobs it queues up next, those are only
+    /// scheduled once this one completes
+    pub async fn run(&self, state: &AppState) -> Result<Vec<Job>, anyhow::Error> {
+        match self {
+            Job::CreateSession {
+                rjti,
+                user_id,
+                email,
+                user_agent,
+                exp,
+                remember_me,
+                location,
+            } => {
+                match database::session::get_by_id(&state.db, rjti).await {
+                    Ok(_) => {}
+                    Err(DbErr::RecordNotFound(_)) => {
+                        database::session::create(
+                            &state.db,
+                            rjti,
+                            user_id,
+                            user_agent,
+                            *exp,
+                            *remember_me,
+                            location.clone(),
+                        )
+                        .await?;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+
+                return Ok(vec![
+                    Job::NotifyNewDevice {
+                        rjti: rjti.clone(),
+                        email: email.clone(),
+                    },
+                    Job::PruneSessions {
+                        user_id: user_id.clone(),
+                    },
+                ]);
+            }
+            Job::NotifyNewDevice { rjti, email } => {
+                let session = match database::session::get_by_id(&state.db, rjti).await {
+                    Ok(session) => session,
+                    // the session was revoked before the email went out
+                    Err(DbErr::RecordNotFound(_)) => return Ok(vec![]),
+                    Err(err) => return Err(err.into()),
+                };
+
+                // a worker that sent the email but died before completing the
+                // job must not send it again when the job is retried
+                if !mark_notified(state, rjti).await? {
+                    return Ok(vec![]);
+                }
+                if let Err(err) = notify_new_device(state, email, &session).await {
+                    unmark_notified(state, rjti).await?;
+                    return Err(err.into());
+                }
+            }
+            Job::PruneSessions { user_id } => {
+                database::session::delete_expired_user_sessions(&state.db, user_id).await?
+            }
+            Job::SendMagicLink { email, nonce } => {
+                send_magic_link_email(state, email, nonce).await?
+            }
+            Job::SendPasswordReset { email } => send_password_reset_email(state, email).await?,
+            Job::RecordLoginRisk {
+                user_id,
+                location,
+                assessment,
+            } => database::risk::create(&state.db, user_id, location, assessment).await?,
+        }
+
+        Ok(vec![])
+    }
+
+    /// called once a job ran out of attempts
+    pub async fn give_up(&self, state: &AppState) {
+        if let Job::CreateSession { rjti, .. } = self {
+            // a refresh token without a session record cannot be listed or
+            // revoked by the user
+            if let Err(err) = Refresh::default(state.clone()).delete(rjti).await {
+                log::error!(
+                    "{}",
+                    anyhow::Error::new(err).context("failed to delete refresh token from redis")
+                )
+            }
+        }
+    }
+}
+
+fn notified_key(rjti: &str) -> String {
+    format!("{}:new_device_notified:{}", &*ENV.redis_schema, rjti)
+}
+
+/// marks the session as notified, false when it already was, the mark
+/// outlives every retry of the job
+async fn mark_notified(state: &AppState, rjti: &str) -> Result<bool, anyhow::Error> {
+    let mut conn = state.get_redis_conn::<anyhow::Error>().await?;
+    let marked: Option<String> = redis::cmd("SET")
+        .arg(notified_key(rjti))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(worker::retry_window(ENV.job_max_attempts).as_secs())
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(marked.is_some())
+}
+
+/// lets the next attempt send the email after this one failed to
+async fn unmark_notified(state: &AppState, rjti: &str) -> Result<(), anyhow::Error> {
+    let mut conn = state.get_redis_conn::<anyhow::Error>().await?;
+    redis::cmd("DEL")
+        .arg(notified_key(rjti))
+        .query_async::<()>(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn enqueue(state: &AppState, job: Job) -> Result<(), anyhow::Error> {
+    queue::enqueue(&state.rd, job).await
+}
@@ -0,0 +1,230 @@
+use super::Job;
+use crate::config::ENV;
+use once_cell::sync::Lazy;
+use redis::{Client, Script, aio::MultiplexedConnection};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+static CLAIM: Lazy<Script> = Lazy::new(|| Script::new(include_str!("scripts/claim.lua")));
+static EXTEND: Lazy<Script> = Lazy::new(|| Script::new(include_str!("scripts/extend.lua")));
+static SETTLE: Lazy<Script> = Lazy::new(|| Script::new(include_str!("scripts/settle.lua")));
+
+/// how long a claimed job stays hidden from other workers, a running job
+/// keeps extending it
+pub const LEASE: Duration = Duration::from_secs(60);
+/// dead jobs kept for inspection
+const DEAD_LETTER_SIZE: isize = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope {
+    pub id: String,
+    pub attempts: u32,
+    pub job: Job,
+    /// proves this worker still holds the job, only set on claimed jobs
+    #[serde(skip)]
+    pub lease: String,
+}
+
+#[derive(Debug, Serialize)]
+struct DeadLetter<'a> {
+    envelope: &'a Envelope,
+    error: String,
+    failed_at: u128,
+}
+
+fn schedule_key() -> String {
+    format!("{}:jobs:schedule", &*ENV.redis_schema)
+}
+
+fn payload_key() -> String {
+    format!("{}:jobs:payload", &*ENV.redis_schema)
+}
+
+fn lease_key() -> String {
+    format!("{}:jobs:lease", &*ENV.redis_schema)
+}
+
+fn dead_key() -> String {
+    format!("{}:jobs:dead", &*ENV.redis_schema)
+}
+
+fn millis(at: SystemTime) -> u128 {
+    at.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+async fn conn(rd: &Client) -> Result<MultiplexedConnection, anyhow::Error> {
+    Ok(rd.get_multiplexed_async_connection().await?)
+}
+
+async fn schedule(rd: &Client, envelope: &Envelope, at: SystemTime) -> Result<(), anyhow::Error> {
+    let mut conn = conn(rd).await?;
+    redis::pipe()
+        .atomic()
+        .hset(
+            payload_key(),
+            &envelope.id,
+            serde_json::to_string(envelope)?,
+        )
+        .ignore()
+        .zadd(schedule_key(), &envelope.id, millis(at) as f64)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+fn wrap(job: Job) -> Envelope {
+    Envelope {
+        id: ulid::Ulid::new().to_string(),
+        attempts: 0,
+        job,
+        lease: String::new(),
+    }
+}
+
+pub async fn enqueue(rd: &Client, job: Job) -> Result<(), anyhow::Error> {
+    schedule(rd, &wrap(job), SystemTime::now()).await
+}
+
+/// leases the next due job, `None` when nothing is due
+pub async fn claim(rd: &Client) -> Result<Option<Envelope>, anyhow::Error> {
+    let mut conn = conn(rd).await?;
+    let now = SystemTime::now();
+    let lease = ulid::Ulid::new().to_string();
+    let payload: Option<String> = CLAIM
+        .key(schedule_key())
+        .key(payload_key())
+        .key(lease_key())
+        .arg(millis(now) as f64)
+        .arg(millis(now + LEASE) as f64)
+        .arg(&lease)
+        .invoke_async(&mut conn)
+        .await?;
+
+    let Some(payload) = payload else {
+        return Ok(None);
+    };
+    let envelope = Envelope {
+        lease,
+        ..serde_json::from_str(&payload)?
+    };
+
+    Ok(Some(envelope))
+}
+
+/// pushes the lease of a running job further out, `false` once another
+/// worker has claimed it
+pub async fn extend(rd: &Client, envelope: &Envelope) -> Result<bool, anyhow::Error> {
+    let mut conn = conn(rd).await?;
+    let extended: bool = EXTEND
+        .key(schedule_key())
+        .key(lease_key())
+        .arg(&envelope.id)
+        .arg(&envelope.lease)
+        .arg(millis(SystemTime::now() + LEASE) as f64)
+        .invoke_async(&mut conn)
+        .await?;
+
+    Ok(extended)
+}
+
+/// removes a job and schedules `next` in its place as one step, nothing
+/// happens and `false` is returned when the lease was lost
+async fn settle(
+    rd: &Client,
+    envelope: &Envelope,
+    next: &[(&Envelope, SystemTime)],
+) -> Result<bool, anyhow::Error> {
+    let mut invocation = SETTLE.prepare_invoke();
+    invocation
+        .key(schedule_key())
+        .key(payload_key())
+        .key(lease_key())
+        .arg(&envelope.id)
+        .arg(&envelope.lease);
+    for (next, at) in next {
+        invocation
+            .arg(&next.id)
+            .arg(millis(*at) as f64)
+            .arg(serde_json::to_string(next)?);
+    }
+
+    let mut conn = conn(rd).await?;
+    Ok(invocation.invoke_async(&mut conn).await?)
+}
+
+/// finishes a job, the jobs it queued up are only scheduled with it so a
+/// job that runs again never queues them twice
+pub async fn complete(
+    rd: &Client,
+    envelope: &Envelope,
+    follow_ups: Vec<Job>,
+) -> Result<bool, anyhow::Error> {
+    let now = SystemTime::now();
+    let follow_ups: Vec<Envelope> = follow_ups.into_iter().map(wrap).collect();
+    let next: Vec<_> = follow_ups.iter().map(|next| (next, now)).collect();
+
+    settle(rd, envelope, &next).await
+}
+
+/// schedules the next attempt of a failed job
+pub async fn retry(
+    rd: &Client,
+    envelope: &Envelope,
+    delay: Duration,
+) -> Result<bool, anyhow::Error> {
+    settle(rd, envelope, &[(envelope, SystemTime::now() + delay)]).await
+}
+
+/// moves a job that ran out of attempts to the dead letter list
+pub async fn bury(rd: &Client, envelope: &Envelope, error: &str) -> Result<bool, anyhow::Error> {
+    let letter = serde_json::to_string(&DeadLetter {
+        envelope,
+        error: error.to_owned(),
+        failed_at: millis(SystemTime::now()),
+    })?;
+
+    if !settle(rd, envelope, &[]).await? {
+        return Ok(false);
+    }
+
+    let mut conn = conn(rd).await?;
+    redis::pipe()
+        .atomic()
+        .lpush(dead_key(), letter)
+        .ignore()
+        .ltrim(dead_key(), 0, DEAD_LETTER_SIZE - 1)
+        .ignore()
+        .query_async::<()>(&mut conn)
+        .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Envelope, Job};
+
+    #[test]
+    fn lease_tokens_are_never_stored_with_the_payload() {
+        let envelope = Envelope {
+            id: String::from("01JQZ6V3W8Y2X4C5B7N9M1K3H5"),
+            attempts: 2,
+            job: Job::PruneSessions {
+                user_id: String::from("user"),
+            },
+            lease: String::from("lease"),
+        };
+
+        let payload = serde_json::to_string(&envelope).unwrap();
+        assert!(!payload.contains("lease"));
+
+        let stored: Envelope = serde_json::from_str(&payload).unwrap();
+        assert_eq!(stored.id, envelope.id);
+        assert_eq!(stored.attempts, 2);
+        assert!(stored.lease.is_empty());
+    }
+}
@@ -0,0 +1,24 @@
+-- KEYS[1] schedule sorted set
+-- KEYS[2] payload hash
+-- KEYS[3] lease hash
+-- ARGV[1] now in milliseconds
+-- ARGV[2] lease expiry in milliseconds
+-- ARGV[3] lease token
+local ids = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, 1)
+if #ids == 0 then
+  return false
+end
+
+-- pushing the score past the lease hides the job from other workers, it
+-- becomes visible again if this worker dies before finishing it
+redis.call('ZADD', KEYS[1], ARGV[2], ids[1])
+local payload = redis.call('HGET', KEYS[2], ids[1])
+if not payload then
+  redis.call('ZREM', KEYS[1], ids[1])
+  redis.call('HDEL', KEYS[3], ids[1])
+  return false
+end
+
+-- a new token fences off whichever worker held the job before
+redis.call('HSET', KEYS[3], ids[1], ARGV[3])
+return payload
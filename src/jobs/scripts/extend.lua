@@ -0,0 +1,11 @@
+-- KEYS[1] schedule sorted set
+-- KEYS[2] lease hash
+-- ARGV[1] job id
+-- ARGV[2] lease token
+-- ARGV[3] new lease expiry in milliseconds
+if redis.call('HGET', KEYS[2], ARGV[1]) ~= ARGV[2] then
+  return 0
+end
+
+redis.call('ZADD', KEYS[1], 'XX', ARGV[3], ARGV[1])
+return 1
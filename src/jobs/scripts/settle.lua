@@ -0,0 +1,19 @@
+-- KEYS[1] schedule sorted set
+-- KEYS[2] payload hash
+-- KEYS[3] lease hash
+-- ARGV[1] job id
+-- ARGV[2] lease token
+-- ARGV[3..] id, score and payload of every job to schedule in its place
+if redis.call('HGET', KEYS[3], ARGV[1]) ~= ARGV[2] then
+  return 0
+end
+
+redis.call('HDEL', KEYS[3], ARGV[1])
+redis.call('ZREM', KEYS[1], ARGV[1])
+redis.call('HDEL', KEYS[2], ARGV[1])
+for i = 3, #ARGV, 3 do
+  redis.call('HSET', KEYS[2], ARGV[i], ARGV[i + 2])
+  redis.call('ZADD', KEYS[1], ARGV[i + 1], ARGV[i])
+end
+
+return 1
@@ -0,0 +1,257 @@
+use super::worker;
+use crate::{
+    config::{ENV, env::TokenStoreKind, state::AppState},
+    database, metrics,
+    util::now,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+const BATCH_SIZE: u64 = 500;
+
+fn lock_key() -> String {
+    format!("{}:sweeper:lock", &*ENV.redis_schema)
+}
+
+/// refresh tokens found without a session record, scored by when a sweep
+/// first saw them that way
+fn orphans_key() -> String {
+    format!("{}:sweeper:orphaned_since", &*ENV.redis_schema)
+}
+
+/// how long a refresh token may go without a session record, the record is
+/// written by a job so this has to outlast every retry of that job
+fn grace_period(interval: Duration) -> Duration {
+    worker::retry_window(ENV.job_max_attempts) + interval
+}
+
+/// claims the current interval for this replica, the lock is left to expire
+/// so the sweep runs at most once per interval across all replicas
+async fn acquire(state: &AppState, interval: Duration) -> Result<bool, anyhow::Error> {
+    let mut conn = state.get_redis_conn::<anyhow::Error>().await?;
+    let acquired: Option<String> = redis::cmd("SET")
+        .arg(lock_key())
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(interval.as_secs())
+        .query_async(&mut conn)
+        .await?;
+
+    Ok(acquired.is_some())
+}
+
+async fn sweep_expired_sessions(state: &AppState, deadline: Instant) -> Result<(), anyhow::Error> {
+    loop {
+        let deleted = database::session::delete_expired(&state.db, BATCH_SIZE).await?;
+        metrics::SWEPT_EXPIRED_SESSIONS.add(deleted);
+        if deleted < BATCH_SIZE || Instant::now() >= deadline {
+            return Ok(());
+        }
+    }
+}
+
+/// splits scanned refresh tokens into ones to revoke because they went
+/// without a session record since before `cutoff`, new orphans to remember
+/// and remembered ones to forget because their session record showed up
+fn classify(
+    scanned: Vec<String>,
+    existing: &HashSet<String>,
+    orphaned_since: &HashMap<String, u64>,
+    cutoff: u64,
+) -> (Vec<String>, Vec<String>, Vec<String>) {
+    let (mut revoke, mut remember, mut forget) = (vec![], vec![], vec![]);
+    for rjti in scanned {
+        match (existing.contains(&rjti), orphaned_since.get(&rjti)) {
+            (true, Some(_)) => forget.push(rjti),
+            (true, None) => {}
+            (false, Some(since)) if *since <= cutoff => revoke.push(rjti),
+            (false, Some(_)) => {}
+            (false, None) => remember.push(rjti),
+        }
+    }
+
+    (revoke, remember, forget)
+}
+
+/// revokes refresh tokens that have gone without a session record for longer
+/// than the grace period, so sessions still being retried in the job queue
+/// are left alone
+async fn sweep_orphaned_tokens(
+    state: &AppState,
+    deadline: Instant,
+    interval: Duration,
+) -> Result<(), anyhow::Error> {
+    let now = now() as u64;
+    let grace = grace_period(interval).as_secs();
+
+    let mut conn = state.get_redis_conn::<anyhow::Error>().await?;
+    let previous: Vec<(String, u64)> = redis::cmd("ZRANGE")
+        .arg(orphans_key())
+        .arg(0)
+        .arg(-1)
+        .arg("WITHSCORES")
+        .query_async(&mut conn)
+        .await?;
+
+    // a sweep cut short by the deadline only sees part of the tokens, so the
+    // set is updated in place and only loses tokens that are gone
+    let rjtis: Vec<String> = previous.iter().map(|(rjti, _)| rjti.clone()).collect();
+    let exists = state.store.refresh_exists(&rjtis).await?;
+    let mut forget: Vec<String> = rjtis
+        .into_iter()
+        .zip(exists)
+        .filter(|(_, exists)| !exists)
+        .map(|(rjti, _)| rjti)
+        .collect();
+    let orphaned_since: HashMap<String, u64> = previous.into_iter().collect();
+
+    let mut remember = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let (next, rjtis) = state
+            .store
+            .scan_refresh(cursor, BATCH_SIZE as usize)
+            .await?;
+        let existing: HashSet<String> = database::session::existing_ids(&state.db, &rjtis)
+            .await?
+            .into_iter()
+            .collect();
+
+        let (revoke, orphans, recorded) =
+            classify(rjtis, &existing, &orphaned_since, now.saturating_sub(grace));
+        for rjti in revoke {
+            if state.store.revoke(&rjti).await? {
+                metrics::SWEPT_ORPHANED_TOKENS.inc();
+            }
+            forget.push(rjti);
+        }
+        remember.extend(orphans);
+        forget.extend(recorded);
+
+        cursor = next;
+        if cursor == 0 || Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    let mut pipe = redis::pipe();
+    pipe.atomic();
+    if !forget.is_empty() {
+        pipe.zrem(orphans_key(), forget).ignore();
+    }
+    if !remember.is_empty() {
+        let members: Vec<(u64, String)> = remember.into_iter().map(|rjti| (now, rjti)).collect();
+        pipe.zadd_multiple(orphans_key(), &members)
+            .ignore()
+            .expire(orphans_key(), (grace + interval.as_secs() * 3) as i64)
+            .ignore();
+    }
+    pipe.query_async::<()>(&mut conn).await?;
+
+    Ok(())
+}
+
+/// deletes active session records whose refresh token no longer exists
+async fn sweep_orphaned_sessions(state: &AppState, deadline: Instant) -> Result<(), anyhow::Error> {
+    let mut after = None;
+    loop {
+        let ids = database::session::active_ids(&state.db, after.as_deref(), BATCH_SIZE).await?;
+        let exists = state.store.refresh_exists(&ids).await?;
+
+        let orphans: Vec<String> = ids
+            .iter()
+            .zip(exists)
+            .filter(|(_, exists)| !exists)
+            .map(|(id, _)| id.clone())
+            .collect();
+        let deleted = database::session::delete_many(&state.db, &orphans).await?;
+        metrics::SWEPT_ORPHANED_SESSIONS.add(deleted);
+
+        if (ids.len() as u64) < BATCH_SIZE || Instant::now() >= deadline {
+            return Ok(());
+        }
+        after = ids.last().cloned();
+    }
+}
+
+async fn sweep(state: &AppState, interval: Duration) -> Result<(), anyhow::Error> {
+    // leave the remaining work to the next sweep well before the lock expires
+    let deadline = Instant::now() + interval / 2;
+
+    sweep_expired_sessions(state, deadline).await?;
+    if !shared(ENV.token_store) {
+        return Ok(());
+    }
+    sweep_orphaned_tokens(state, deadline, interval).await?;
+    sweep_orphaned_sessions(state, deadline).await
+}
+
+/// whether every replica sees the same tokens, a replica with a memory store
+/// would take the sessions of every other replica for orphans
+fn shared(token_store: TokenStoreKind) -> bool {
+    token_store != TokenStoreKind::Memory
+}
+
+/// periodically deletes expired sessions and reconciles refresh tokens with
+/// session records, runs until the process exits
+pub async fn run(state: AppState) {
+    let interval = Duration::from_secs(ENV.sweeper_interval);
+    if !shared(ENV.token_store) {
+        log::warn!(
+            "tokens are kept in process memory, orphaned tokens and sessions will not be swept"
+        );
+    }
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+        match acquire(&state, interval).await {
+            Ok(true) => {
+                if let Err(err) = sweep(&state, interval).await {
+                    log::error!("{:#}", err.context("failed to sweep sessions"));
+                }
+            }
+            Ok(false) => {}
+            Err(err) => log::error!("{:#}", err.context("failed to acquire the sweeper lock")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, shared};
+    use crate::config::env::TokenStoreKind;
+    use std::collections::{HashMap, HashSet};
+
+    fn set(rjtis: &[&str]) -> HashSet<String> {
+        rjtis.iter().map(|rjti| rjti.to_string()).collect()
+    }
+
+    #[test]
+    fn only_tokens_orphaned_past_the_grace_period_are_revoked() {
+        let scanned = ["new", "old", "retrying", "recorded", "late"]
+            .map(String::from)
+            .to_vec();
+        let existing = set(&["recorded", "late"]);
+        let orphaned_since = HashMap::from([
+            (String::from("old"), 100),
+            (String::from("retrying"), 500),
+            (String::from("late"), 100),
+            (String::from("unscanned"), 100),
+        ]);
+
+        let (revoke, remember, forget) = classify(scanned, &existing, &orphaned_since, 300);
+        assert_eq!(revoke, ["old"]);
+        assert_eq!(remember, ["new"]);
+        assert_eq!(forget, ["late"]);
+    }
+
+    #[test]
+    fn orphan_sweeps_need_a_shared_token_store() {
+        assert!(shared(TokenStoreKind::Redis));
+        assert!(!shared(TokenStoreKind::Memory));
+    }
+}
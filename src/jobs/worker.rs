@@ -0,0 +1,132 @@
+use super::{
+    Job,
+    queue::{self, Envelope},
+};
+use crate::config::{ENV, state::AppState};
+use std::time::Duration;
+
+/// how long an idle worker waits before polling again
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(10 * 60);
+
+/// exponential backoff starting at two seconds
+fn backoff(attempts: u32) -> Duration {
+    Duration::from_secs(2u64.saturating_pow(attempts)).min(MAX_BACKOFF)
+}
+
+/// the longest a job can take from its first attempt to its last one, when
+/// every attempt holds its lease to the end and then fails
+pub fn retry_window(max_attempts: u32) -> Duration {
+    (1..max_attempts).map(backoff).sum::<Duration>() + queue::LEASE * max_attempts
+}
+
+/// runs a job while keeping its lease alive
+async fn run(state: &AppState, envelope: &Envelope) -> Result<Vec<Job>, anyhow::Error> {
+    let job = envelope.job.run(state);
+    tokio::pin!(job);
+
+    let mut heartbeat = tokio::time::interval(queue::LEASE / 3);
+    heartbeat.tick().await;
+    loop {
+        tokio::select! {
+            result = &mut job => return result,
+            _ = heartbeat.tick() => match queue::extend(&state.rd, envelope).await {
+                Ok(true) => {}
+                Ok(false) => log::warn!("job {} was claimed by another worker", envelope.id),
+                Err(err) => log::warn!("{:#}", err.context(format!("failed to extend the lease of job {}", envelope.id))),
+            },
+        }
+    }
+}
+
+/// a job settled after losing its lease is left to the worker holding it
+fn settled(envelope: &Envelope, settled: bool) -> Result<(), anyhow::Error> {
+    if !settled {
+        log::warn!(
+            "job {} was claimed by another worker, dropping this result",
+            envelope.id
+        );
+    }
+    Ok(())
+}
+
+async fn process(state: &AppState, mut envelope: Envelope) -> Result<(), anyhow::Error> {
+    let err = match run(state, &envelope).await {
+        Ok(follow_ups) => {
+            let done = queue::complete(&state.rd, &envelope, follow_ups).await?;
+            return settled(&envelope, done);
+        }
+        Err(err) => err,
+    };
+
+    envelope.attempts += 1;
+    if envelope.attempts < ENV.job_max_attempts {
+        log::warn!(
+            "{:#}",
+            err.context(format!(
+                "job {} failed on attempt {}, retrying",
+                envelope.id, envelope.attempts
+            ))
+        );
+        let done = queue::retry(&state.rd, &envelope, backoff(envelope.attempts)).await?;
+        return settled(&envelope, done);
+    }
+
+    let error = format!("{:#}", err);
+    log::error!(
+        "job {} failed {} times and was moved to the dead letter list: {}",
+        envelope.id,
+        envelope.attempts,
+        error
+    );
+    if !queue::bury(&state.rd, &envelope, &error).await? {
+        return settled(&envelope, false);
+    }
+    envelope.job.give_up(state).await;
+    Ok(())
+}
+
+async fn work(state: AppState) {
+    loop {
+        match queue::claim(&state.rd).await {
+            Ok(Some(envelope)) => {
+                if let Err(err) = process(&state, envelope).await {
+                    log::error!("{:#}", err.context("failed to settle a job"));
+                }
+            }
+            Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+            Err(err) => {
+                log::error!("{:#}", err.context("failed to claim a job"));
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// starts the in-process worker pool, jobs a worker was holding when the
+/// process stopped are picked up again once their lease expires
+pub fn start(state: AppState) {
+    for _ in 0..ENV.job_workers {
+        tokio::spawn(work(state.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MAX_BACKOFF, backoff, queue, retry_window};
+    use std::time::Duration;
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(1), Duration::from_secs(2));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn retry_window_covers_every_backoff_and_lease() {
+        assert_eq!(retry_window(1), queue::LEASE);
+        // 2 + 4 + 8 + 16 + 32 + 64 + 128 seconds of backoff plus 8 leases
+        assert_eq!(retry_window(8), Duration::from_secs(254) + queue::LEASE * 8);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::error::AppError;
+use ldap3::{LdapConnAsync, LdapConnSettings, Scope, SearchEntry, dn_escape, ldap_escape};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct DirectoryUser {
+    pub email: String,
+    pub username: String,
+    pub name: String,
+}
+
+/// whether `email` belongs to `domain`, directories can only vouch for
+/// addresses in the domain they are configured for
+fn in_domain(email: &str, domain: &str) -> bool {
+    email.rsplit_once('@').is_some_and(|(local, email_domain)| {
+        !local.is_empty() && email_domain.eq_ignore_ascii_case(domain)
+    })
+}
+
+pub async fn authenticate(
+    directory: &entity::directory::Model,
+    credential: &str,
+    password: &str,
+) -> Result<DirectoryUser, AppError> {
+    // an empty password turns a simple bind into an unauthenticated bind,
+    // which most servers accept
+    if password.is_empty() {
+        return Err(AppError::IncorrectCredentials(anyhow::anyhow!(
+            "password is required"
+        )));
+    }
+
+    let credential = credential.to_lowercase();
+    let username = credential
+        .split_once('@')
+        .map_or(credential.as_str(), |(username, _)| username);
+
+    let settings = LdapConnSettings::new().set_conn_timeout(TIMEOUT);
+    let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &directory.url)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+    ldap3::drive!(conn);
+
+    let bind_dn = directory
+        .bind_dn
+        .replace("{username}", &dn_escape(username))
+        .replace("{email}", &dn_escape(credential.as_str()));
+    ldap.simple_bind(&bind_dn, password)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?
+        .success()
+        .map_err(|err| AppError::IncorrectCredentials(err.into()))?;
+
+    let filter = if credential.contains('@') {
+        format!(
+            "({}={})",
+            directory.email_attribute,
+            ldap_escape(credential.as_str())
+        )
+    } else {
+        format!(
+            "({}={})",
+            directory.username_attribute,
+            ldap_escape(username)
+        )
+    };
+    let (entries, _) = ldap
+        .search(
+            &directory.base_dn,
+            Scope::Subtree,
+            &filter,
+            vec![
+                directory.email_attribute.as_str(),
+                directory.username_attribute.as_str(),
+                directory.name_attribute.as_str(),
+            ],
+        )
+        .await
+        .map_err(|err| AppError::Other(err.into()))?
+        .success()
+        .map_err(|err| AppError::Other(err.into()))?;
+    let _ = ldap.unbind().await;
+
+    let entry = entries
+        .into_iter()
+        .next()
+        .map(SearchEntry::construct)
+        .ok_or(AppError::NotFound(anyhow::anyhow!(
+            "user does not exist in the directory"
+        )))?;
+    let attribute = |name: &str| {
+        entry
+            .attrs
+            .get(name)
+            .and_then(|values| values.first())
+            .cloned()
+    };
+
+    let email = attribute(&directory.email_attribute)
+        .ok_or(AppError::BadRequest(anyhow::anyhow!(
+            "directory entry does not have an email address"
+        )))?
+        .to_lowercase();
+    if !in_domain(&email, &directory.domain) {
+        return Err(AppError::Unauthorized(anyhow::anyhow!(
+            "directory returned an email address outside of its domain"
+        )));
+    }
+
+    let username: String = attribute(&directory.username_attribute)
+        .unwrap_or_else(|| username.to_owned())
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .take(20)
+        .collect::<String>()
+        .to_lowercase();
+    let name = attribute(&directory.name_attribute).unwrap_or_else(|| username.clone());
+
+    Ok(DirectoryUser {
+        email,
+        username,
+        name,
+    })
+}
+
+#[cfg(test)]
+mod tests;
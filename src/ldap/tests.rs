@@ -0,0 +1,232 @@
+//! runs `authenticate` against an in-process ldap stand-in that understands
+//! just enough of the protocol for a simple bind followed by a search
+
+use super::{authenticate, in_domain};
+use crate::error::AppError;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const BIND_REQUEST: u8 = 0x60;
+const UNBIND_REQUEST: u8 = 0x42;
+const SEARCH_REQUEST: u8 = 0x63;
+const BIND_RESPONSE: u8 = 0x61;
+const SEARCH_RESULT_ENTRY: u8 = 0x64;
+const SEARCH_RESULT_DONE: u8 = 0x65;
+
+const SUCCESS: u8 = 0;
+const INVALID_CREDENTIALS: u8 = 49;
+
+#[derive(Clone)]
+struct StandIn {
+    bind_dn: &'static str,
+    password: &'static str,
+    entry: Vec<(&'static str, &'static str)>,
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    match content.len() {
+        len if len < 0x80 => out.push(len as u8),
+        len => {
+            let bytes: Vec<u8> = len
+                .to_be_bytes()
+                .into_iter()
+                .skip_while(|byte| *byte == 0)
+                .collect();
+            out.push(0x80 | bytes.len() as u8);
+            out.extend(bytes);
+        }
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+/// splits a buffer into (tag, content) pairs
+fn elements(mut buf: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut out = vec![];
+    while buf.len() >= 2 {
+        let tag = buf[0];
+        let (len, header) = match buf[1] {
+            len if len < 0x80 => (len as usize, 2),
+            len => {
+                let n = (len & 0x7f) as usize;
+                let len = buf[2..2 + n]
+                    .iter()
+                    .fold(0, |acc, byte| (acc << 8) | *byte as usize);
+                (len, 2 + n)
+            }
+        };
+        out.push((tag, &buf[header..header + len]));
+        buf = &buf[header + len..];
+    }
+    out
+}
+
+async fn read_message(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).await.ok()?;
+    let mut message = header.to_vec();
+    let len = match header[1] {
+        len if len < 0x80 => len as usize,
+        len => {
+            let mut bytes = vec![0; (len & 0x7f) as usize];
+            stream.read_exact(&mut bytes).await.ok()?;
+            message.extend_from_slice(&bytes);
+            bytes
+                .iter()
+                .fold(0, |acc, byte| (acc << 8) | *byte as usize)
+        }
+    };
+    let mut content = vec![0; len];
+    stream.read_exact(&mut content).await.ok()?;
+    message.extend(content);
+    Some(message)
+}
+
+fn respond(id: &[u8], op: Vec<u8>) -> Vec<u8> {
+    tlv(0x30, &[tlv(0x02, id), op].concat())
+}
+
+fn result(tag: u8, code: u8) -> Vec<u8> {
+    tlv(
+        tag,
+        &[tlv(0x0a, &[code]), tlv(0x04, b""), tlv(0x04, b"")].concat(),
+    )
+}
+
+async fn serve(mut stream: TcpStream, stand_in: StandIn) {
+    while let Some(message) = read_message(&mut stream).await {
+        let outer = elements(&message);
+        let parts = elements(outer[0].1);
+        let (id, (op, body)) = (parts[0].1, parts[1]);
+
+        let reply = match op {
+            BIND_REQUEST => {
+                let fields = elements(body);
+                let (dn, password) = (fields[1].1, fields[2].1);
+                let code = match dn == stand_in.bind_dn.as_bytes()
+                    && password == stand_in.password.as_bytes()
+                {
+                    true => SUCCESS,
+                    false => INVALID_CREDENTIALS,
+                };
+                respond(id, result(BIND_RESPONSE, code))
+            }
+            SEARCH_REQUEST => {
+                let attributes: Vec<u8> = stand_in
+                    .entry
+                    .iter()
+                    .flat_map(|(name, value)| {
+                        tlv(
+                            0x30,
+                            &[
+                                tlv(0x04, name.as_bytes()),
+                                tlv(0x31, &tlv(0x04, value.as_bytes())),
+                            ]
+                            .concat(),
+                        )
+                    })
+                    .collect();
+                let entry = tlv(
+                    SEARCH_RESULT_ENTRY,
+                    &[
+                        tlv(0x04, b"uid=jane,dc=acme,dc=com"),
+                        tlv(0x30, &attributes),
+                    ]
+                    .concat(),
+                );
+                [
+                    respond(id, entry),
+                    respond(id, result(SEARCH_RESULT_DONE, SUCCESS)),
+                ]
+                .concat()
+            }
+            UNBIND_REQUEST => return,
+            _ => return,
+        };
+        if stream.write_all(&reply).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn start(stand_in: StandIn) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ldap://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(serve(stream, stand_in.clone()));
+        }
+    });
+    url
+}
+
+fn directory(url: String) -> entity::directory::Model {
+    entity::directory::Model {
+        id: String::from("directory"),
+        provider_id: String::from("acme"),
+        domain: String::from("acme.com"),
+        url,
+        bind_dn: String::from("uid={username},dc=acme,dc=com"),
+        base_dn: String::from("dc=acme,dc=com"),
+        email_attribute: String::from("mail"),
+        username_attribute: String::from("uid"),
+        name_attribute: String::from("cn"),
+    }
+}
+
+fn stand_in(mail: &'static str) -> StandIn {
+    StandIn {
+        bind_dn: "uid=jane,dc=acme,dc=com",
+        password: "correct horse",
+        entry: vec![("mail", mail), ("uid", "jane"), ("cn", "Jane Doe")],
+    }
+}
+
+#[tokio::test]
+async fn authenticates_a_directory_user() {
+    let url = start(stand_in("Jane@Acme.com")).await;
+
+    let user = authenticate(&directory(url), "jane@acme.com", "correct horse")
+        .await
+        .unwrap();
+    assert_eq!(user.email, "jane@acme.com");
+    assert_eq!(user.username, "jane");
+    assert_eq!(user.name, "Jane Doe");
+}
+
+#[tokio::test]
+async fn rejects_a_wrong_password() {
+    let url = start(stand_in("jane@acme.com")).await;
+
+    let result = authenticate(&directory(url), "jane", "wrong").await;
+    assert!(matches!(result, Err(AppError::IncorrectCredentials(_))));
+}
+
+#[tokio::test]
+async fn rejects_an_empty_password_without_binding() {
+    let directory = directory(String::from("ldap://127.0.0.1:1"));
+
+    let result = authenticate(&directory, "jane", "").await;
+    assert!(matches!(result, Err(AppError::IncorrectCredentials(_))));
+}
+
+#[tokio::test]
+async fn rejects_an_email_outside_of_the_directory_domain() {
+    let url = start(stand_in("ceo@other-tenant.com")).await;
+
+    let result = authenticate(&directory(url), "jane", "correct horse").await;
+    assert!(matches!(result, Err(AppError::Unauthorized(_))));
+}
+
+#[test]
+fn in_domain_matches_the_whole_domain() {
+    assert!(in_domain("jane@acme.com", "acme.com"));
+    assert!(in_domain("jane@ACME.com", "acme.com"));
+    assert!(!in_domain("jane@notacme.com", "acme.com"));
+    assert!(!in_domain("jane@acme.com.evil.io", "acme.com"));
+    assert!(!in_domain("@acme.com", "acme.com"));
+    assert!(!in_domain("acme.com", "acme.com"));
+}
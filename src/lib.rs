@@ -1,7 +1,12 @@
 pub mod config;
 pub mod database;
 pub mod error;
+pub mod geo;
+pub mod jobs;
+pub mod ldap;
+pub mod metrics;
 pub mod model;
+pub mod risk;
 pub mod service;
 pub mod template;
 pub mod token;
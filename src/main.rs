@@ -4,13 +4,18 @@ use auth_rs::config::state::AppState;
 use auth_rs::util::shutdown_signal;
 use auth_rs::{
     config::ENV,
+    jobs, metrics,
     service::{admin, auth},
 };
+use std::time::Duration;
 use tonic::transport::Server;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let state = AppState::new().await;
+    tokio::spawn(metrics::report(Duration::from_secs(300)));
+    jobs::start(state.clone());
+    tokio::spawn(jobs::sweeper::run(state.clone()));
 
     println!("server running on [::1]:{}", ENV.port);
 
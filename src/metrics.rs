@@ -0,0 +1,74 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// a monotonically increasing process wide counter
+pub struct Counter {
+    name: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: AtomicU64::new(0),
+        }
+    }
+
+    pub fn inc(&self) {
+        self.value.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn add(&self, value: u64) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+pub static GEOIP_CACHE_HITS: Counter = Counter::new("geoip_cache_hits");
+pub static GEOIP_CACHE_MISSES: Counter = Counter::new("geoip_cache_misses");
+pub static GEOIP_BREAKER_REJECTIONS: Counter = Counter::new("geoip_breaker_rejections");
+pub static USER_AGENT_CACHE_HITS: Counter = Counter::new("user_agent_cache_hits");
+pub static USER_AGENT_CACHE_MISSES: Counter = Counter::new("user_agent_cache_misses");
+pub static SWEPT_EXPIRED_SESSIONS: Counter = Counter::new("swept_expired_sessions");
+pub static SWEPT_ORPHANED_TOKENS: Counter = Counter::new("swept_orphaned_tokens");
+pub static SWEPT_ORPHANED_SESSIONS: Counter = Counter::new("swept_orphaned_sessions");
+
+static COUNTERS: [&Counter; 8] = [
+    &GEOIP_CACHE_HITS,
+    &GEOIP_CACHE_MISSES,
+    &GEOIP_BREAKER_REJECTIONS,
+    &USER_AGENT_CACHE_HITS,
+    &USER_AGENT_CACHE_MISSES,
+    &SWEPT_EXPIRED_SESSIONS,
+    &SWEPT_ORPHANED_TOKENS,
+    &SWEPT_ORPHANED_SESSIONS,
+];
+
+pub fn snapshot() -> Vec<(&'static str, u64)> {
+    COUNTERS
+        .iter()
+        .map(|counter| (counter.name, counter.get()))
+        .collect()
+}
+
+/// logs every counter once per `interval`, runs until the process exits
+pub async fn report(interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+        let line = snapshot()
+            .into_iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join(" ");
+        log::info!("metrics {}", line);
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{
-    admin_proto::{CreateAdminRequest, DeleteAdminRequest},
+    admin_proto::{
+        CreateAdminRequest, DeleteAdminRequest, RevokeUserTokensRequest, SetMaxSessionsRequest,
+        SetPasswordResetRequiredRequest,
+    },
     util::verify,
 };
 use serde::{Deserialize, Serialize};
@@ -48,3 +51,77 @@ impl From<DeleteAdminRequest> for DeleteAdminReq {
         }
     }
 }
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct RevokeUserTokensReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+
+    #[validate(custom(function = "verify::otp"))]
+    pub otp: String,
+
+    #[validate(length(min = 26, max = 26, message = "must be a valid user id"))]
+    pub user_id: String,
+}
+
+impl From<RevokeUserTokensRequest> for RevokeUserTokensReq {
+    fn from(value: RevokeUserTokensRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            user_id: value.user_id,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct SetMaxSessionsReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+
+    #[validate(custom(function = "verify::otp"))]
+    pub otp: String,
+
+    #[validate(length(min = 26, max = 26, message = "must be a valid user id"))]
+    pub user_id: String,
+
+    /// `None` falls back to the global limit, 0 removes the limit
+    #[validate(range(max = 1000, message = "must be at most 1000"))]
+    pub max_sessions: Option<u32>,
+}
+
+impl From<SetMaxSessionsRequest> for SetMaxSessionsReq {
+    fn from(value: SetMaxSessionsRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            user_id: value.user_id,
+            max_sessions: value.max_sessions,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct SetPasswordResetRequiredReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+
+    #[validate(custom(function = "verify::otp"))]
+    pub otp: String,
+
+    #[validate(length(min = 26, max = 26, message = "must be a valid user id"))]
+    pub user_id: String,
+
+    pub required: bool,
+}
+
+impl From<SetPasswordResetRequiredRequest> for SetPasswordResetRequiredReq {
+    fn from(value: SetPasswordResetRequiredRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            user_id: value.user_id,
+            required: value.required,
+        }
+    }
+}
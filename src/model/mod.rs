@@ -1,3 +1,6 @@
 pub mod admin;
 pub mod api;
+pub mod role;
+pub mod session;
+pub mod token;
 pub mod user;
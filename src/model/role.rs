@@ -0,0 +1,111 @@
+use crate::{
+    admin_proto::{AssignRoleRequest, CreateRoleRequest, DeleteRoleRequest, RevokeRoleRequest},
+    util::verify,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Grants {
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+impl Grants {
+    pub fn scope(&self) -> String {
+        self.permissions.join(" ")
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct CreateRoleReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+
+    #[validate(custom(function = "verify::otp"))]
+    pub otp: String,
+
+    #[validate(custom(function = "verify::role"))]
+    pub name: String,
+
+    #[validate(length(
+        min = 5,
+        max = 150,
+        message = "description must be between 5 and 150 characters"
+    ))]
+    pub description: String,
+
+    #[validate(custom(function = "verify::permissions"))]
+    pub permissions: Vec<String>,
+}
+
+impl From<CreateRoleRequest> for CreateRoleReq {
+    fn from(value: CreateRoleRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            name: value.name,
+            description: value.description,
+            permissions: value.permissions,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct DeleteRoleReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+
+    #[validate(custom(function = "verify::otp"))]
+    pub otp: String,
+
+    #[validate(custom(function = "verify::role"))]
+    pub name: String,
+}
+
+impl From<DeleteRoleRequest> for DeleteRoleReq {
+    fn from(value: DeleteRoleRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            name: value.name,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct UserRoleReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+
+    #[validate(custom(function = "verify::otp"))]
+    pub otp: String,
+
+    #[validate(length(min = 26, max = 26, message = "must be a valid user id"))]
+    pub user_id: String,
+
+    #[validate(custom(function = "verify::role"))]
+    pub role: String,
+}
+
+impl From<AssignRoleRequest> for UserRoleReq {
+    fn from(value: AssignRoleRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            user_id: value.user_id,
+            role: value.role,
+        }
+    }
+}
+
+impl From<RevokeRoleRequest> for UserRoleReq {
+    fn from(value: RevokeRoleRequest) -> Self {
+        Self {
+            email: value.email,
+            otp: value.otp,
+            user_id: value.user_id,
+            role: value.role,
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::auth_proto::{
+    ListSessionsRequest, ReportSessionRequest, RevokeSessionRequest,
+    list_sessions_response::Session,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct ListSessionsReq {
+    #[validate(length(min = 1, message = "access token is required"))]
+    pub access_token: String,
+
+    #[validate(range(min = 1, message = "page must be at least 1"))]
+    pub page: u64,
+
+    #[validate(range(min = 1, max = 100, message = "page size must be between 1 and 100"))]
+    pub page_size: u64,
+}
+
+impl From<ListSessionsRequest> for ListSessionsReq {
+    fn from(value: ListSessionsRequest) -> Self {
+        Self {
+            access_token: value.access_token,
+            page: value.page.unwrap_or(1),
+            page_size: value.page_size.unwrap_or(20),
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct RevokeSessionReq {
+    #[validate(length(min = 1, message = "access token is required"))]
+    pub access_token: String,
+
+    #[validate(length(min = 26, max = 26, message = "must be a valid session id"))]
+    pub session_id: String,
+}
+
+impl From<RevokeSessionRequest> for RevokeSessionReq {
+    fn from(value: RevokeSessionRequest) -> Self {
+        Self {
+            access_token: value.access_token,
+            session_id: value.session_id,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct ReportSessionReq {
+    #[validate(length(min = 1, message = "token is required"))]
+    pub token: String,
+}
+
+impl From<ReportSessionRequest> for ReportSessionReq {
+    fn from(value: ReportSessionRequest) -> Self {
+        Self { token: value.token }
+    }
+}
+
+/// the session a "this wasn't me" link was sent for
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionReport {
+    pub user_id: String,
+    pub rjti: String,
+}
+
+impl Session {
+    pub fn from_model(value: entity::session::Model, rjti: &str) -> Self {
+        Self {
+            current: value.id == rjti,
+            id: value.id,
+            ip_address: value.ip_address,
+            login_at: value.login_at as u64,
+            expires: value.exp as u64,
+            device_vendor: value.device_vendor,
+            device_model: value.device_model,
+            os_name: value.os_name,
+            os_version: value.os_version,
+            browser_name: value.browser_name,
+            browser_version: value.borwser_version,
+            country: value.country,
+            city: value.city,
+            region: value.region,
+            timezone: value.timezone,
+            lat: value.lat.map(|lat| lat.to_string()),
+            lon: value.lon.map(|lon| lon.to_string()),
+            map_url: value.map_url,
+            last_seen_at: value.last_seen_at as u64,
+            last_ip_address: value.last_ip_address,
+            refresh_count: value.refresh_count as u64,
+        }
+    }
+}
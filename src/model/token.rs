@@ -0,0 +1,84 @@
+use crate::{
+    auth_proto::{ExchangeTokenRequest, IntrospectTokenRequest, RefreshRequest},
+    util::verify,
+};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct ExchangeTokenReq {
+    #[validate(length(min = 1, message = "subject token is required"))]
+    pub subject_token: String,
+
+    #[validate(length(min = 1, message = "actor token is required"))]
+    pub actor_token: String,
+
+    #[validate(length(
+        min = 1,
+        max = 255,
+        message = "audience must be between 1 and 255 characters"
+    ))]
+    pub audience: Option<String>,
+
+    #[validate(length(
+        min = 1,
+        max = 1000,
+        message = "scope must be between 1 and 1000 characters"
+    ))]
+    pub scope: Option<String>,
+}
+
+impl From<ExchangeTokenRequest> for ExchangeTokenReq {
+    fn from(value: ExchangeTokenRequest) -> Self {
+        Self {
+            subject_token: value.subject_token,
+            actor_token: value.actor_token,
+            audience: value.audience,
+            scope: value.scope,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct IntrospectTokenReq {
+    #[validate(length(min = 1, message = "token is required"))]
+    pub token: String,
+
+    #[validate(length(
+        min = 1,
+        max = 255,
+        message = "audience must be between 1 and 255 characters"
+    ))]
+    pub audience: String,
+
+    #[validate(custom(function = "verify::token_type_hint"))]
+    pub token_type_hint: Option<String>,
+}
+
+impl From<IntrospectTokenRequest> for IntrospectTokenReq {
+    fn from(value: IntrospectTokenRequest) -> Self {
+        Self {
+            token: value.token,
+            audience: value.audience,
+            token_type_hint: value.token_type_hint,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct RefreshReq {
+    #[validate(length(min = 1, message = "refresh token is required"))]
+    pub refresh_token: String,
+
+    #[validate(ip(message = "not a valid ip address"))]
+    pub ip_address: Option<String>,
+}
+
+impl From<RefreshRequest> for RefreshReq {
+    fn from(value: RefreshRequest) -> Self {
+        Self {
+            refresh_token: value.refresh_token,
+            ip_address: value.ip_address,
+        }
+    }
+}
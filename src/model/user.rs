@@ -1,4 +1,10 @@
-use crate::{auth_proto::RegisterRequest, util::verify};
+use crate::{
+    auth_proto::{
+        ForgotPasswordRequest, RedeemMagicLinkRequest, RegisterRequest, ResetPasswordRequest,
+        SendMagicLinkRequest, VerifyForgotPasswordTokenRequest,
+    },
+    util::verify,
+};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -55,6 +61,9 @@ pub struct UserDetails {
 
     pub is_two_factor_enabled: bool,
     pub is_email_verified: bool,
+
+    #[serde(default)]
+    pub profile_version: i32,
 }
 
 impl From<&UserDetails> for UserDetails {
@@ -67,6 +76,7 @@ impl From<&UserDetails> for UserDetails {
             photo_url: value.photo_url.clone(),
             is_email_verified: value.is_email_verified,
             is_two_factor_enabled: value.is_two_factor_enabled,
+            profile_version: value.profile_version,
         }
     }
 }
@@ -87,6 +97,7 @@ impl From<entity::user::Model> for UserDetails {
             },
             is_two_factor_enabled: value.is_two_factor_enabled,
             is_email_verified: value.is_email_verified,
+            profile_version: value.profile_version,
         }
     }
 }
@@ -107,6 +118,96 @@ impl From<&entity::user::Model> for UserDetails {
             },
             is_two_factor_enabled: value.is_two_factor_enabled,
             is_email_verified: value.is_email_verified,
+            profile_version: value.profile_version,
+        }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct SendMagicLinkReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+}
+
+impl From<SendMagicLinkRequest> for SendMagicLinkReq {
+    fn from(value: SendMagicLinkRequest) -> Self {
+        Self { email: value.email }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct RedeemMagicLinkReq {
+    #[validate(length(min = 43, max = 43, message = "must be a valid token"))]
+    pub token: String,
+
+    #[validate(length(min = 43, max = 43, message = "must be a valid nonce"))]
+    pub nonce: String,
+
+    pub otp: Option<String>,
+    pub audience: Option<String>,
+    pub ip_address: String,
+    pub user_agent: Option<String>,
+    pub remember_me: Option<bool>,
+}
+
+impl From<RedeemMagicLinkRequest> for RedeemMagicLinkReq {
+    fn from(value: RedeemMagicLinkRequest) -> Self {
+        Self {
+            token: value.token,
+            nonce: value.nonce,
+            otp: value.otp,
+            audience: value.audience,
+            ip_address: value.ip_address,
+            user_agent: value.user_agent,
+            remember_me: value.remember_me,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MagicLink {
+    pub user_id: String,
+    pub nonce: String,
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct ForgotPasswordReq {
+    #[validate(email(message = "not valid"))]
+    pub email: String,
+}
+
+impl From<ForgotPasswordRequest> for ForgotPasswordReq {
+    fn from(value: ForgotPasswordRequest) -> Self {
+        Self { email: value.email }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct VerifyForgotPasswordTokenReq {
+    #[validate(length(min = 43, max = 43, message = "must be a valid token"))]
+    pub token: String,
+}
+
+impl From<VerifyForgotPasswordTokenRequest> for VerifyForgotPasswordTokenReq {
+    fn from(value: VerifyForgotPasswordTokenRequest) -> Self {
+        Self { token: value.token }
+    }
+}
+
+#[derive(Debug, Validate, Serialize, Deserialize)]
+pub struct ResetPasswordReq {
+    #[validate(length(min = 43, max = 43, message = "must be a valid token"))]
+    pub token: String,
+
+    #[validate(custom(function = "verify::password"))]
+    pub password: String,
+}
+
+impl From<ResetPasswordRequest> for ResetPasswordReq {
+    fn from(value: ResetPasswordRequest) -> Self {
+        Self {
+            token: value.token,
+            password: value.password,
         }
     }
 }
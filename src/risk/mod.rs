@@ -0,0 +1,159 @@
+use crate::{
+    config::{ENV, env::RiskAction},
+    database,
+    geo::Location,
+};
+use sea_orm::{DatabaseConnection, DbErr};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// logins scoring at least this much are considered high risk
+const HIGH_RISK_SCORE: i32 = 50;
+/// sessions the new login is compared against
+const RECENT_SESSIONS: u64 = 20;
+/// faster than a commercial flight, in km/h
+const MAX_TRAVEL_SPEED: f64 = 1000.0;
+/// geolocation of an ip address is only accurate to a few hundred kilometers
+const MIN_TRAVEL_DISTANCE: f64 = 300.0;
+const EARTH_RADIUS: f64 = 6371.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    ImpossibleTravel,
+    NewCountry,
+    NewAsn,
+}
+
+impl Signal {
+    fn weight(self) -> i32 {
+        match self {
+            Signal::ImpossibleTravel => 60,
+            Signal::NewCountry => 30,
+            Signal::NewAsn => 20,
+        }
+    }
+}
+
+impl Display for Signal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Signal::ImpossibleTravel => write!(f, "impossible_travel"),
+            Signal::NewCountry => write!(f, "new_country"),
+            Signal::NewAsn => write!(f, "new_asn"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Allow,
+    Challenge,
+    Block,
+}
+
+impl Display for Decision {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Decision::Allow => write!(f, "allow"),
+            Decision::Challenge => write!(f, "challenge"),
+            Decision::Block => write!(f, "block"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assessment {
+    pub score: i32,
+    pub signals: Vec<Signal>,
+    pub decision: Decision,
+}
+
+impl Assessment {
+    fn new(signals: Vec<Signal>) -> Self {
+        let score = signals.iter().map(|signal| signal.weight()).sum();
+        let decision = match (score >= HIGH_RISK_SCORE, ENV.high_risk_login_action) {
+            (false, _) => Decision::Allow,
+            (true, RiskAction::Challenge) => Decision::Challenge,
+            (true, RiskAction::Block) => Decision::Block,
+        };
+
+        Self {
+            score,
+            signals,
+            decision,
+        }
+    }
+}
+
+/// great circle distance between two coordinates in km
+fn distance((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (lon2 - lon1).to_radians();
+
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+fn coordinates(session: &entity::session::Model) -> Option<(f64, f64)> {
+    let lat = f64::try_from(session.lat?).ok()?;
+    let lon = f64::try_from(session.lon?).ok()?;
+
+    Some((lat, lon))
+}
+
+/// scores a login against the user's recent sessions, a user without previous
+/// sessions has nothing to compare with and is always allowed
+pub async fn assess(
+    db: &DatabaseConnection,
+    user_id: &str,
+    location: &Location,
+    now: usize,
+) -> Result<Assessment, DbErr> {
+    let recent = database::session::recent(db, user_id, RECENT_SESSIONS).await?;
+    let mut signals = vec![];
+    if recent.is_empty() {
+        return Ok(Assessment::new(signals));
+    }
+
+    let here = location
+        .lat
+        .zip(location.lon)
+        .map(|(lat, lon)| (lat as f64, lon as f64));
+    let last = recent
+        .iter()
+        .find_map(|session| coordinates(session).map(|there| (session, there)));
+    if let (Some(here), Some((session, there))) = (here, last) {
+        let distance = distance(here, there);
+        let last_seen = session.last_seen_at.max(session.login_at) as usize;
+        // at least a minute apart so back to back logins do not divide by zero
+        let hours = (now.saturating_sub(last_seen).max(60)) as f64 / 3600.0;
+        if distance > MIN_TRAVEL_DISTANCE && distance / hours > MAX_TRAVEL_SPEED {
+            signals.push(Signal::ImpossibleTravel);
+        }
+    }
+
+    if let Some(country) = location.country.as_deref().filter(|c| !c.is_empty())
+        && !recent
+            .iter()
+            .any(|session| session.country.as_deref() == Some(country))
+    {
+        signals.push(Signal::NewCountry);
+    }
+
+    // sessions created before asns were recorded cannot be compared
+    let known = recent
+        .iter()
+        .filter_map(|session| session.asn.as_deref())
+        .collect::<Vec<_>>();
+    if let Some(asn) = location.asn.as_deref()
+        && !known.is_empty()
+        && !known.contains(&asn)
+    {
+        signals.push(Signal::NewAsn);
+    }
+
+    Ok(Assessment::new(signals))
+}
@@ -1,16 +1,25 @@
 use crate::admin_proto::{
-    CreateAdminRequest, CreateAdminResponse, CreateApiKeyRequest, CreateApiKeyResponse,
+    AssignRoleRequest, AssignRoleResponse, CreateAdminRequest, CreateAdminResponse,
+    CreateApiKeyRequest, CreateApiKeyResponse, CreateRoleRequest, CreateRoleResponse,
     DeleteAdminRequest, DeleteAdminResponse, DeleteApiKeyRequest, DeleteApiKeyResponse,
-    ListApiKeysRequest, ListApiKeysResponse, SendEmailResponse,
+    DeleteRoleRequest, DeleteRoleResponse, ListApiKeysRequest, ListApiKeysResponse,
+    RevokeRoleRequest, RevokeRoleResponse, RevokeUserTokensRequest, RevokeUserTokensResponse,
+    SendEmailResponse, SetMaxSessionsRequest, SetMaxSessionsResponse,
+    SetPasswordResetRequiredRequest, SetPasswordResetRequiredResponse,
 };
 use crate::admin_proto::{SendEmailRequest, admin_service_server::AdminService};
 use crate::config::ENV;
 use crate::config::state::AppState;
 use crate::database;
 use crate::error::AppError;
-use crate::model::admin::{CreateAdminReq, DeleteAdminReq};
+use crate::model::admin::{
+    CreateAdminReq, DeleteAdminReq, RevokeUserTokensReq, SetMaxSessionsReq,
+    SetPasswordResetRequiredReq,
+};
 use crate::model::api::{CreateApiKeyReq, DeleteApiKeyReq, ListApiKeysReq};
+use crate::model::role::{CreateRoleReq, DeleteRoleReq, UserRoleReq};
 use crate::template::email::send_otp;
+use crate::token::epoch;
 use crate::util::{generate_otp, validate_otp};
 use resend_rs::types::CreateEmailBaseOptions;
 use tonic::{Request, Response, Status};
@@ -166,4 +175,142 @@ impl AdminService for Service {
 
         Ok(Response::new(DeleteApiKeyResponse {}))
     }
+
+    async fn create_role(
+        &self,
+        request: Request<CreateRoleRequest>,
+    ) -> Result<Response<CreateRoleResponse>, Status> {
+        let request: CreateRoleReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        database::role::create(
+            &self.state.db,
+            &request.name,
+            &request.description,
+            &request.permissions,
+        )
+        .await
+        .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(CreateRoleResponse {}))
+    }
+
+    async fn delete_role(
+        &self,
+        request: Request<DeleteRoleRequest>,
+    ) -> Result<Response<DeleteRoleResponse>, Status> {
+        let request: DeleteRoleReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        database::role::delete(&self.state.db, &request.name)
+            .await
+            .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(DeleteRoleResponse {}))
+    }
+
+    async fn assign_role(
+        &self,
+        request: Request<AssignRoleRequest>,
+    ) -> Result<Response<AssignRoleResponse>, Status> {
+        let request: UserRoleReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        database::role::assign(&self.state.db, &request.user_id, &request.role)
+            .await
+            .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(AssignRoleResponse {}))
+    }
+
+    async fn revoke_role(
+        &self,
+        request: Request<RevokeRoleRequest>,
+    ) -> Result<Response<RevokeRoleResponse>, Status> {
+        let request: UserRoleReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        database::role::revoke(&self.state.db, &request.user_id, &request.role)
+            .await
+            .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(RevokeRoleResponse {}))
+    }
+
+    async fn revoke_user_tokens(
+        &self,
+        request: Request<RevokeUserTokensRequest>,
+    ) -> Result<Response<RevokeUserTokensResponse>, Status> {
+        let request: RevokeUserTokensReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        let epoch = epoch::revoke_all(&self.state, &request.user_id)
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        Ok(Response::new(RevokeUserTokensResponse {
+            // the contract reports seconds, tokens issued from this second on
+            // are valid
+            tokens_valid_after: epoch.div_ceil(1000),
+        }))
+    }
+
+    async fn set_max_sessions(
+        &self,
+        request: Request<SetMaxSessionsRequest>,
+    ) -> Result<Response<SetMaxSessionsResponse>, Status> {
+        let request: SetMaxSessionsReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        database::user::set_max_sessions(
+            &self.state.db,
+            &request.user_id,
+            request.max_sessions.map(|max| max as i32),
+        )
+        .await
+        .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(SetMaxSessionsResponse {}))
+    }
+
+    /// directory accounts reset their password in the directory, so the
+    /// requirement has to be lifted here once that happened
+    async fn set_password_reset_required(
+        &self,
+        request: Request<SetPasswordResetRequiredRequest>,
+    ) -> Result<Response<SetPasswordResetRequiredResponse>, Status> {
+        let request: SetPasswordResetRequiredReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        validate_otp(self.state.clone(), &otp_key(&request.email), &request.otp).await?;
+
+        database::user::set_password_reset_required(
+            &self.state.db,
+            &request.user_id,
+            request.required,
+        )
+        .await
+        .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(SetPasswordResetRequiredResponse {}))
+    }
 }
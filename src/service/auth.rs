@@ -2,21 +2,51 @@ use crate::{
     auth_proto::{
         ChangeEmailRequest, ChangeEmailResponse, ChangePasswordRequest, ChangePasswordResponse,
         ChangeUsernameRequest, ChangeUsernameResponse, DeleteRequest, DeleteResponse,
-        ForgotPasswordRequest, ForgotPasswordResponse, LoginRequest, LoginResponse, LogoutRequest,
-        LogoutResponse, ReauthTokenRequest, ReauthTokenResponse, RefreshRequest, RefreshResponse,
-        RegisterRequest, RegisterResponse, ResetPasswordResponse,
+        ExchangeTokenRequest, ExchangeTokenResponse, ForgotPasswordRequest, ForgotPasswordResponse,
+        IntrospectTokenRequest, IntrospectTokenResponse, ListSessionsRequest, ListSessionsResponse,
+        LoginRequest, LoginResponse, LogoutRequest, LogoutResponse, ReauthTokenRequest,
+        ReauthTokenResponse, RedeemMagicLinkRequest, RefreshRequest, RefreshResponse,
+        RegisterRequest, RegisterResponse, ReportSessionRequest, ReportSessionResponse,
+        ResetPasswordRequest, ResetPasswordResponse, RevokeSessionRequest, RevokeSessionResponse,
         SendEmailVerificationForNewEmailRequest, SendEmailVerificationForNewEmailResponse,
-        SendEmailVerificationRequest, SendEmailVerificationResponse, Token,
-        VerifyEmailTokenRequest, VerifyEmailTokenResponse, VerifyForgotPasswordTokenRequest,
-        VerifyForgotPasswordTokenResponse, VerifyTokenRequest, VerifyTokenResponse,
-        auth_service_server::AuthService, login_response::Tokens,
+        SendEmailVerificationRequest, SendEmailVerificationResponse, SendMagicLinkRequest,
+        SendMagicLinkResponse, Token, VerifyEmailTokenRequest, VerifyEmailTokenResponse,
+        VerifyForgotPasswordTokenRequest, VerifyForgotPasswordTokenResponse, VerifyTokenRequest,
+        VerifyTokenResponse, auth_service_server::AuthService,
+        list_sessions_response::Session as SessionDetails, login_response::Tokens,
     },
-    config::{ENV, state::AppState},
+    config::{ENV, env::SessionLimitAction, state::AppState},
     database,
     error::AppError,
-    model::user::{CreateUserReq, UserDetails},
-    token::{claims::Claims, service::factory, types::refresh::Refresh},
+    geo::{self, Location},
+    jobs::{self, Job},
+    ldap,
+    model::{
+        session::{ListSessionsReq, ReportSessionReq, RevokeSessionReq, SessionReport},
+        token::{ExchangeTokenReq, IntrospectTokenReq, RefreshReq},
+        user::{
+            CreateUserReq, ForgotPasswordReq, MagicLink, RedeemMagicLinkReq, ResetPasswordReq,
+            SendMagicLinkReq, UserDetails, VerifyForgotPasswordTokenReq,
+        },
+    },
+    risk::{self, Decision},
+    template::email::{magic_link, new_device, password_reset, send_otp},
+    token::{
+        Audience, TokenType, activity,
+        claims::Claims,
+        dpop::Proof,
+        epoch, lifetime,
+        params::TokenParams,
+        service::{create_token, factory},
+        store::SessionCap,
+        traits::Token as _,
+        types::{access::Access, refresh::Refresh, session::Session},
+    },
+    util::{generate_otp, generate_secret, hash_secret, now},
 };
+use resend_rs::types::CreateEmailBaseOptions;
+use sea_orm::DbErr;
+use time::{OffsetDateTime, format_description::well_known::Rfc2822};
 use tonic::{Request, Response, Status};
 use validator::Validate;
 
@@ -31,6 +61,524 @@ impl Service {
     }
 }
 
+fn magic_link_key(hash: &str) -> String {
+    format!("{}:magic_link:{}", &*ENV.redis_schema, hash)
+}
+
+fn password_reset_key(hash: &str) -> String {
+    format!("{}:password_reset:{}", &*ENV.redis_schema, hash)
+}
+
+fn session_report_key(hash: &str) -> String {
+    format!("{}:session_report:{}", &*ENV.redis_schema, hash)
+}
+
+fn risk_otp_key(user_id: &str) -> String {
+    format!("{}:risk:otp:{}", &*ENV.redis_schema, user_id)
+}
+
+/// "this wasn't me" links stay valid for a week
+const SESSION_REPORT_EXPIRATION: usize = 7 * 24 * 60 * 60;
+
+/// emails a sign in link to the account with the given email, `nonce` is the
+/// hash of the nonce handed to the device that requested the link
+pub async fn send_magic_link_email(
+    state: &AppState,
+    email: &str,
+    nonce: &str,
+) -> Result<(), AppError> {
+    let user = match database::user::get_by_email(&state.db, email).await {
+        Ok(user) => user,
+        Err(DbErr::RecordNotFound(_)) => return Ok(()),
+        Err(err) => return Err(AppError::from_database_error(err)),
+    };
+
+    let token = generate_secret();
+    let value = serde_json::to_string(&MagicLink {
+        user_id: user.id,
+        nonce: nonce.to_owned(),
+    })
+    .map_err(AppError::from_generic_error)?;
+
+    let mut conn = state.get_redis_conn().await.map_err(AppError::Other)?;
+    let _: () = redis::cmd("SET")
+        .arg(magic_link_key(&hash_secret(&token)))
+        .arg(value)
+        .arg("EX")
+        .arg(ENV.magic_link_expiration)
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+
+    let link = format!("{}?token={}", &*ENV.magic_link_url, &token);
+    let email = CreateEmailBaseOptions::new(
+        &*ENV.resend_email,
+        [&user.email],
+        "Your sign in link for auth_rs",
+    )
+    .with_html(
+        magic_link(&link, ENV.magic_link_expiration / 60)
+            .into_string()
+            .as_str(),
+    );
+
+    state
+        .resend
+        .emails
+        .send(email)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+
+    Ok(())
+}
+
+/// emails a password reset link to the account with the given email, accounts
+/// without a local password have nothing to reset
+pub async fn send_password_reset_email(state: &AppState, email: &str) -> Result<(), AppError> {
+    let user = match database::user::get_by_email(&state.db, email).await {
+        Ok(user) => user,
+        Err(DbErr::RecordNotFound(_)) => return Ok(()),
+        Err(err) => return Err(AppError::from_database_error(err)),
+    };
+    if user.password.is_none() {
+        return Ok(());
+    }
+
+    let token = generate_secret();
+    let mut conn = state.get_redis_conn().await.map_err(AppError::Other)?;
+    let _: () = redis::cmd("SET")
+        .arg(password_reset_key(&hash_secret(&token)))
+        .arg(&user.id)
+        .arg("EX")
+        .arg(ENV.password_reset_expiration)
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+
+    let link = format!("{}?token={}", &*ENV.password_reset_url, &token);
+    let email = CreateEmailBaseOptions::new(
+        &*ENV.resend_email,
+        [&user.email],
+        "Reset your auth_rs password",
+    )
+    .with_html(
+        password_reset(&link, ENV.password_reset_expiration / 60)
+            .into_string()
+            .as_str(),
+    );
+
+    state
+        .resend
+        .emails
+        .send(email)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+
+    Ok(())
+}
+
+/// when a session that has no record yet expires, sessions are recorded by a
+/// background job after login so until then the refresh token ttl in the
+/// store is the only place its lifetime lives
+fn unrecorded_exp(remaining: Option<usize>, now: usize) -> usize {
+    now + remaining.unwrap_or_default()
+}
+
+/// the session cap of a user, their own limit wins over the global one and a
+/// limit of 0 means no cap
+fn session_cap(
+    max_sessions: Option<i32>,
+    default: usize,
+    action: SessionLimitAction,
+) -> Option<SessionCap> {
+    let limit = match max_sessions {
+        Some(limit) => limit.max(0) as usize,
+        None => default,
+    };
+
+    (limit > 0).then_some(SessionCap { limit, action })
+}
+
+/// whether a reported session forces a password reset, accounts without a
+/// local password could never complete one and sign in through links or a
+/// directory, so revoking their tokens is all that is left to do
+fn must_reset_password(user: &entity::user::Model) -> bool {
+    user.password.is_some()
+}
+
+/// a reported session leaves the password compromised, so no login path may
+/// issue tokens until it has been reset
+fn require_current_password(user: &entity::user::Model) -> Result<(), AppError> {
+    // a flag left on an account without a local password could never be
+    // cleared by its owner
+    if user.password_reset_required && must_reset_password(user) {
+        return Err(AppError::Unauthorized(anyhow::anyhow!(
+            "password must be reset before signing in"
+        )));
+    }
+
+    Ok(())
+}
+
+/// emails the user about a session from a device or location they have not
+/// signed in from before, with a link to report it
+pub async fn notify_new_device(
+    state: &AppState,
+    email: &str,
+    session: &entity::session::Model,
+) -> Result<(), AppError> {
+    if !database::session::is_new_device(&state.db, session)
+        .await
+        .map_err(AppError::from_database_error)?
+    {
+        return Ok(());
+    }
+
+    let token = generate_secret();
+    let value = serde_json::to_string(&SessionReport {
+        user_id: session.user_id.clone(),
+        rjti: session.id.clone(),
+    })
+    .map_err(AppError::from_generic_error)?;
+
+    let mut conn = state.get_redis_conn().await.map_err(AppError::Other)?;
+    let _: () = redis::cmd("SET")
+        .arg(session_report_key(&hash_secret(&token)))
+        .arg(value)
+        .arg("EX")
+        .arg(SESSION_REPORT_EXPIRATION)
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+
+    let unknown = String::from("Unknown");
+    let device = format!(
+        "{} on {}",
+        session.browser_name.as_ref().unwrap_or(&unknown),
+        session.os_name.as_ref().unwrap_or(&unknown)
+    );
+    let location = [&session.city, &session.region, &session.country]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.is_empty())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    let location = match location.is_empty() {
+        true => session.ip_address.clone(),
+        false => format!("{} ({})", location, session.ip_address),
+    };
+    let time = OffsetDateTime::from_unix_timestamp(session.login_at.into())
+        .ok()
+        .and_then(|time| time.format(&Rfc2822).ok())
+        .unwrap_or_default();
+
+    let link = format!("{}?token={}", &*ENV.session_report_url, &token);
+    let email = CreateEmailBaseOptions::new(
+        &*ENV.resend_email,
+        [email],
+        "New sign in to your auth_rs account",
+    )
+    .with_html(
+        new_device(&device, &location, &time, &link)
+            .into_string()
+            .as_str(),
+    );
+
+    state
+        .resend
+        .emails
+        .send(email)
+        .await
+        .map_err(|err| AppError::Other(err.into()))?;
+
+    Ok(())
+}
+
+fn introspection<C: Claims>(claims: &C) -> IntrospectTokenResponse {
+    IntrospectTokenResponse {
+        active: true,
+        sub: Some(claims.sub().to_owned()),
+        aud: Some(claims.aud().to_owned()),
+        iss: Some(claims.iss().to_owned()),
+        scope: claims.scope().map(str::to_owned),
+        roles: claims.roles().map(<[String]>::to_vec).unwrap_or_default(),
+        exp: Some(claims.exp() as u64),
+        iat: Some(claims.iat() as u64),
+        jti: Some(claims.jti().to_owned()),
+        jkt: claims.cnf().map(|cnf| cnf.jkt.clone()),
+    }
+}
+
+impl Service {
+    async fn verify_two_factor(
+        &self,
+        user: &entity::user::Model,
+        otp: Option<String>,
+    ) -> Result<(), AppError> {
+        if !user.is_two_factor_enabled {
+            return Ok(());
+        }
+
+        let otp = otp.ok_or(AppError::OTPRequired(anyhow::anyhow!(
+            "OTP is required to login"
+        )))?;
+
+        let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
+        let value: Option<String> = redis::cmd("GET")
+            .arg(format!("{}:twofactor:otp:{}", &*ENV.redis_schema, &otp))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        value.ok_or(AppError::OTPInvalid(anyhow::anyhow!("OTP is invalid")))?;
+
+        Ok(())
+    }
+
+    /// compares the login with the user's recent sessions, high risk logins are
+    /// either blocked or have to confirm an otp sent by email, users with two
+    /// factor enabled have already confirmed theirs
+    async fn assess_risk(
+        &self,
+        user: &entity::user::Model,
+        location: &Location,
+        otp: Option<&str>,
+    ) -> Result<(), AppError> {
+        let assessment = match risk::assess(&self.state.db, &user.id, location, now()).await {
+            Ok(assessment) => assessment,
+            Err(err) => {
+                log::error!(
+                    "{}",
+                    anyhow::Error::new(err).context("failed to assess the login risk")
+                );
+                return Ok(());
+            }
+        };
+
+        if let Err(err) = jobs::enqueue(
+            &self.state,
+            Job::RecordLoginRisk {
+                user_id: user.id.clone(),
+                location: location.clone(),
+                assessment: assessment.clone(),
+            },
+        )
+        .await
+        {
+            log::error!(
+                "{:#}",
+                err.context("failed to enqueue the login risk record")
+            )
+        }
+
+        match assessment.decision {
+            Decision::Allow => Ok(()),
+            Decision::Block => Err(AppError::Unauthorized(anyhow::anyhow!(
+                "sign in was blocked because it looks suspicious"
+            ))),
+            Decision::Challenge if user.is_two_factor_enabled => Ok(()),
+            Decision::Challenge => self.challenge(user, otp).await,
+        }
+    }
+
+    async fn challenge(
+        &self,
+        user: &entity::user::Model,
+        otp: Option<&str>,
+    ) -> Result<(), AppError> {
+        let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
+
+        if let Some(otp) = otp {
+            let value: Option<String> = redis::cmd("GETDEL")
+                .arg(risk_otp_key(&user.id))
+                .query_async(&mut conn)
+                .await
+                .map_err(|err| AppError::Other(err.into()))?;
+            if value.as_deref() != Some(otp) {
+                return Err(AppError::OTPInvalid(anyhow::anyhow!("OTP is invalid")));
+            }
+
+            return Ok(());
+        }
+
+        let otp = generate_otp();
+        let _: () = redis::cmd("SET")
+            .arg(risk_otp_key(&user.id))
+            .arg(&otp)
+            .arg("EX")
+            .arg(10 * 60)
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+
+        let email = CreateEmailBaseOptions::new(
+            &*ENV.resend_email,
+            [&user.email],
+            format!("[{}] Confirm your sign in to auth_rs", &otp),
+        )
+        .with_html(
+            send_otp(&otp, "confirm a sign in from a new location")
+                .into_string()
+                .as_str(),
+        );
+        self.state
+            .resend
+            .emails
+            .send(email)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+
+        Err(AppError::OTPRequired(anyhow::anyhow!(
+            "OTP sent to your email is required to login"
+        )))
+    }
+
+    async fn provision(
+        &self,
+        directory: &entity::directory::Model,
+        credential: &str,
+        password: &str,
+    ) -> Result<entity::user::Model, AppError> {
+        let directory_user = ldap::authenticate(directory, credential, password).await?;
+
+        match database::user::get_by_email(&self.state.db, &directory_user.email).await {
+            Ok(user) => {
+                // local, oauth and other directories' accounts with the same
+                // email must not be reachable through this directory
+                let managed =
+                    database::user::has_provider(&self.state.db, &user.id, &directory.provider_id)
+                        .await
+                        .map_err(AppError::from_database_error)?;
+                if !managed {
+                    return Err(AppError::InvalidProvider(anyhow::anyhow!(
+                        "account is not managed by this directory"
+                    )));
+                }
+
+                Ok(user)
+            }
+            Err(DbErr::RecordNotFound(_)) => database::user::create(
+                &self.state.db,
+                &directory.provider_id,
+                &directory_user.email,
+                &directory_user.username,
+                &directory_user.name,
+                None,
+                None,
+            )
+            .await
+            .map_err(|err| match AppError::from_database_error(err) {
+                AppError::UniqueViolation(_) => AppError::UniqueViolation(anyhow::anyhow!(
+                    "username {} is already taken by another account",
+                    directory_user.username
+                )),
+                err => err,
+            }),
+            Err(err) => Err(AppError::from_database_error(err)),
+        }
+    }
+
+    /// returns the key thumbprint the issued tokens should be bound to when the
+    /// client presented a dpop proof
+    async fn thumbprint(&self, proof: Option<Proof>) -> Result<Option<String>, AppError> {
+        match proof {
+            Some(proof) => proof
+                .verify(&self.state, None)
+                .await
+                .map(Some)
+                .map_err(AppError::from_token_error),
+            None => Ok(None),
+        }
+    }
+
+    async fn issue(
+        &self,
+        user: entity::user::Model,
+        audience: Option<String>,
+        jkt: Option<String>,
+        remember_me: bool,
+        location: Location,
+        user_agent: Option<String>,
+    ) -> Result<LoginResponse, AppError> {
+        let cap = session_cap(
+            user.max_sessions,
+            ENV.max_sessions,
+            ENV.session_limit_action,
+        );
+        let user: UserDetails = user.into();
+        let audience = match audience {
+            Some(audience) if !ENV.token_audiences.contains(&audience) => {
+                return Err(AppError::BadRequest(anyhow::anyhow!(
+                    "audience is not registered"
+                )));
+            }
+            Some(audience) => audience,
+            None => ENV.token_audiences[0].clone(),
+        };
+
+        let tokens = factory(
+            self.state.clone(),
+            &user,
+            &audience,
+            jkt.as_deref(),
+            remember_me,
+            cap,
+        )
+        .await?;
+
+        let login_at = tokens.refresh.claims.iat();
+        let exp = lifetime::expires_at(remember_me, login_at, login_at);
+
+        let rjti = tokens.refresh.claims.jti.clone();
+        if let Err(err) = jobs::enqueue(
+            &self.state,
+            Job::CreateSession {
+                rjti: rjti.clone(),
+                user_id: user.id,
+                email: user.email,
+                user_agent: user_agent.unwrap_or_default(),
+                exp,
+                remember_me,
+                location,
+            },
+        )
+        .await
+        {
+            // without a session record the refresh token could never be listed
+            // or revoked, so the login fails instead
+            if let Err(err) = Refresh::default(self.state.clone()).delete(&rjti).await {
+                log::error!(
+                    "{}",
+                    anyhow::Error::new(err).context("failed to delete refresh token from redis")
+                )
+            }
+
+            return Err(AppError::Other(
+                err.context("failed to enqueue the session record"),
+            ));
+        }
+
+        Ok(LoginResponse {
+            tokens: Some(Tokens {
+                // the token itself is signed for the absolute cap, the session
+                // ends earlier unless it keeps being refreshed
+                refresh: Some(Token {
+                    token: tokens.refresh.token,
+                    expires: exp as u64,
+                }),
+                access: Some(Token {
+                    token: tokens.access.token,
+                    expires: tokens.access.claims.exp() as u64,
+                }),
+                session: Some(Token {
+                    token: tokens.session.token,
+                    expires: tokens.session.claims.exp() as u64,
+                }),
+            }),
+        })
+    }
+}
+
 #[tonic::async_trait]
 impl AuthService for Service {
     async fn register(
@@ -61,104 +609,159 @@ impl AuthService for Service {
         &self,
         request: Request<LoginRequest>,
     ) -> Result<Response<LoginResponse>, Status> {
+        let proof = Proof::from_request(&request, "/auth.AuthService/Login");
         let request = request.into_inner();
-        let state = self.state.clone();
+        let jkt = self.thumbprint(proof).await?;
 
-        let user = database::user::get_by_credential(&state.db, &request.credential)
-            .await
-            .map_err(AppError::from_database_error)?;
-        let password = user
-            .password
-            .clone()
-            .ok_or(AppError::InvalidProvider(anyhow::anyhow!(
-                "canot login with password, you have used another login provider"
-            )))?;
-
-        bcrypt::verify(&request.password, &password)
-            .map_err(|err| AppError::IncorrectCredentials(err.into()))?;
-
-        if user.is_two_factor_enabled {
-            let otp = request.otp.ok_or(AppError::OTPRequired(anyhow::anyhow!(
-                "OTP is required to login"
-            )))?;
-
-            let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
-            let value: Option<String> = redis::cmd("GET")
-                .arg(format!("{}:twofactor:otp:{}", &*ENV.redis_schema, &otp))
-                .query_async(&mut conn)
+        let directory =
+            database::directory::find_for_credential(&self.state.db, &request.credential)
                 .await
-                .map_err(|err| AppError::Other(err.into()))?;
-            value.ok_or(AppError::OTPInvalid(anyhow::anyhow!("OTP is invalid")))?;
-        }
+                .map_err(AppError::from_database_error)?;
 
-        let user: UserDetails = user.into();
-        let tokens = factory(self.state.clone(), &user).await?;
+        let user = match directory {
+            Some(directory) => {
+                self.provision(&directory, &request.credential, &request.password)
+                    .await?
+            }
+            None => {
+                let user = database::user::get_by_credential(&self.state.db, &request.credential)
+                    .await
+                    .map_err(AppError::from_database_error)?;
+                let password =
+                    user.password
+                        .clone()
+                        .ok_or(AppError::InvalidProvider(anyhow::anyhow!(
+                            "canot login with password, you have used another login provider"
+                        )))?;
 
-        let rjti = tokens.refresh.claims.jti.clone();
+                bcrypt::verify(&request.password, &password)
+                    .map_err(|err| AppError::IncorrectCredentials(err.into()))?;
 
-        tokio::spawn(async move {
-            if let Err(err) = database::session::create(
-                &state.db,
-                &rjti,
-                &user.id,
-                &request.ip_address,
-                request.user_agent.as_deref().unwrap_or(""),
+                user
+            }
+        };
+        require_current_password(&user)?;
+
+        self.verify_two_factor(&user, request.otp.clone()).await?;
+        let location = geo::locate(&*self.state.geoip, &request.ip_address).await;
+        self.assess_risk(&user, &location, request.otp.as_deref())
+            .await?;
+
+        let response = self
+            .issue(
+                user,
+                request.audience,
+                jkt,
+                request.remember_me.unwrap_or(true),
+                location,
+                request.user_agent,
+            )
+            .await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn refresh(
+        &self,
+        request: Request<RefreshRequest>,
+    ) -> Result<Response<RefreshResponse>, Status> {
+        let proof = Proof::from_request(&request, "/auth.AuthService/Refresh");
+        let request: RefreshReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        let claims = Refresh::default(self.state.clone())
+            .verify(
+                &request.refresh_token,
+                TokenType::Refresh,
+                Audience::Registered,
+                proof.as_ref(),
             )
             .await
-            {
-                if let Err(err) = Refresh::default(state.clone()).delete(&rjti).await {
-                    log::error!(
-                        "{}",
-                        anyhow::Error::new(err)
-                            .context("failed to delete refresh token from redis")
-                    )
-                }
+            .map_err(AppError::from_token_error)?;
 
-                log::error!(
-                    "{}",
-                    anyhow::Error::new(err)
-                        .context("failed to create the session record in the database")
-                )
+        let remember_me = match database::session::get_by_id(&self.state.db, claims.jti()).await {
+            Ok(session) => Some(session.remember_me),
+            Err(DbErr::RecordNotFound(_)) => None,
+            Err(err) => return Err(AppError::from_database_error(err).into()),
+        };
+        let now = now();
+        let exp = match remember_me {
+            Some(remember_me) => lifetime::expires_at(remember_me, claims.iat(), now),
+            None => {
+                let remaining = self
+                    .state
+                    .store
+                    .ttl(TokenType::Refresh, claims.jti())
+                    .await
+                    .map_err(AppError::from_token_error)?;
+                unrecorded_exp(remaining, now)
             }
+        };
+        if exp <= now {
+            return Err(AppError::Unauthorized(anyhow::anyhow!("session has expired")).into());
+        }
 
-            if let Err(err) =
-                database::session::delete_expired_user_sessions(&state.db, &user.id).await
-            {
+        let user = database::user::get_by_id(&self.state.db, claims.sub())
+            .await
+            .map_err(AppError::from_database_error)?;
+        let grants = database::role::get_grants(&self.state.db, &user.id)
+            .await
+            .map_err(AppError::from_database_error)?;
+
+        let access = Access::new(self.state.clone(), &user.id)
+            .refresh(
+                claims.jti(),
+                claims.aud(),
+                claims.cnf().map(|cnf| cnf.jkt.clone()),
+                grants.clone(),
+                exp - now,
+            )
+            .await
+            .map_err(AppError::from_token_error)?;
+        database::session::set_exp(&self.state.db, claims.jti(), exp)
+            .await
+            .map_err(AppError::from_database_error)?;
+
+        let state = self.state.clone();
+        let rjti = claims.jti().to_owned();
+        let ip_address = request.ip_address.clone();
+        tokio::spawn(async move {
+            if let Err(err) = activity::record(&state, &rjti, ip_address.as_deref()).await {
                 log::error!(
                     "{}",
-                    anyhow::Error::new(err)
-                        .context("failed to delete expired user sessions from the database")
+                    anyhow::Error::new(err).context("failed to record the session activity")
                 )
             }
         });
 
-        Ok(Response::new(LoginResponse {
-            tokens: Some(Tokens {
-                refresh: Some(Token {
-                    token: tokens.refresh.token,
-                    expires: tokens.refresh.claims.exp() as u64,
-                }),
-                access: Some(Token {
-                    token: tokens.access.token,
-                    expires: tokens.access.claims.exp() as u64,
-                }),
-                session: Some(Token {
-                    token: tokens.session.token,
-                    expires: tokens.session.claims.exp() as u64,
-                }),
+        // the session token embeds the profile, so it is reissued with every
+        // refresh to pick up changes made since the last one
+        let user: UserDetails = user.into();
+        let profile_version = user.profile_version;
+        let session = create_token(
+            Session::new(self.state.clone(), user),
+            TokenParams::default()
+                .with_aud(claims.aud().to_owned())
+                .with_grants(grants),
+        )
+        .await?;
+
+        Ok(Response::new(RefreshResponse {
+            access: Some(Token {
+                token: access.token().to_owned(),
+                expires: access.claims().exp() as u64,
+            }),
+            session: Some(Token {
+                token: session.token().to_owned(),
+                expires: session.claims().exp() as u64,
             }),
+            profile_version: profile_version as u64,
+            refresh_expires: exp as u64,
         }))
     }
 
-    async fn refresh(
-        &self,
-        request: Request<RefreshRequest>,
-    ) -> Result<Response<RefreshResponse>, Status> {
-        let _request = request.into_inner();
-
-        todo!()
-    }
-
     async fn reauth_token(
         &self,
         request: Request<ReauthTokenRequest>,
@@ -226,27 +829,78 @@ impl AuthService for Service {
         &self,
         request: Request<VerifyForgotPasswordTokenRequest>,
     ) -> Result<Response<VerifyForgotPasswordTokenResponse>, Status> {
-        let _request = request.into_inner();
+        let request: VerifyForgotPasswordTokenReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
 
-        todo!()
+        let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(password_reset_key(&hash_secret(&request.token)))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        if !exists {
+            return Err(AppError::Unauthorized(anyhow::anyhow!(
+                "reset link is invalid or has expired"
+            ))
+            .into());
+        }
+
+        Ok(Response::new(VerifyForgotPasswordTokenResponse {}))
     }
 
     async fn forgot_password(
         &self,
         request: Request<ForgotPasswordRequest>,
     ) -> Result<Response<ForgotPasswordResponse>, Status> {
-        let _request = request.into_inner();
+        let request: ForgotPasswordReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
 
-        todo!()
+        // like sign in links, the lookup happens in the background so the
+        // response does not reveal whether the account exists
+        jobs::enqueue(
+            &self.state,
+            Job::SendPasswordReset {
+                email: request.email,
+            },
+        )
+        .await
+        .map_err(AppError::Other)?;
+
+        Ok(Response::new(ForgotPasswordResponse {}))
     }
 
     async fn reset_password(
         &self,
-        request: Request<ResetPasswordResponse>,
+        request: Request<ResetPasswordRequest>,
     ) -> Result<Response<ResetPasswordResponse>, Status> {
-        let _request = request.into_inner();
+        let request: ResetPasswordReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
 
-        todo!()
+        let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
+        let user_id: Option<String> = redis::cmd("GETDEL")
+            .arg(password_reset_key(&hash_secret(&request.token)))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        let user_id = user_id.ok_or(AppError::Unauthorized(anyhow::anyhow!(
+            "reset link is invalid or has expired"
+        )))?;
+
+        database::user::reset_password(&self.state.db, &user_id, &request.password)
+            .await
+            .map_err(AppError::from_database_error)?;
+        // whoever knew the old password may still hold tokens
+        epoch::revoke_all(&self.state, &user_id)
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        Ok(Response::new(ResetPasswordResponse {}))
     }
 
     async fn change_email(
@@ -275,4 +929,416 @@ impl AuthService for Service {
 
         todo!()
     }
+
+    async fn exchange_token(
+        &self,
+        request: Request<ExchangeTokenRequest>,
+    ) -> Result<Response<ExchangeTokenResponse>, Status> {
+        let proof = Proof::from_request(&request, "/auth.AuthService/ExchangeToken");
+        let request: ExchangeTokenReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        let access = Access::default(self.state.clone());
+        let subject = access
+            .verify(
+                &request.subject_token,
+                TokenType::Access,
+                Audience::Registered,
+                proof.as_ref(),
+            )
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        // the dpop proof of the request is bound to the subject token, so a
+        // sender-constrained actor token could not be proven as well
+        let actor = access
+            .inspect(&request.actor_token, Audience::Registered)
+            .await
+            .map_err(AppError::from_token_error)?;
+        if actor.cnf().is_some() {
+            return Err(AppError::BadRequest(anyhow::anyhow!(
+                "actor token must not be sender-constrained"
+            ))
+            .into());
+        }
+
+        let exchanged = access
+            .exchange(&subject, &actor, request.audience, request.scope)
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        Ok(Response::new(ExchangeTokenResponse {
+            access: Some(Token {
+                token: exchanged.token().to_owned(),
+                expires: exchanged.claims().exp() as u64,
+            }),
+            issued_token_type: String::from("urn:ietf:params:oauth:token-type:access_token"),
+            scope: exchanged.claims().scope.clone(),
+        }))
+    }
+
+    async fn introspect_token(
+        &self,
+        request: Request<IntrospectTokenRequest>,
+    ) -> Result<Response<IntrospectTokenResponse>, Status> {
+        let request: IntrospectTokenReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        // inactive tokens carry no further information
+        let response = match request.token_type_hint.as_deref() {
+            Some("session_token") => {
+                let session = Session::default(self.state.clone());
+                session
+                    .verify(
+                        &request.token,
+                        TokenType::Session,
+                        Audience::Exact(&request.audience),
+                        None,
+                    )
+                    .await
+                    .map(|claims| introspection(&claims))
+            }
+            // the resource server checks the dpop binding against the returned jkt
+            _ => Access::default(self.state.clone())
+                .inspect(&request.token, Audience::Exact(&request.audience))
+                .await
+                .map(|claims| introspection(&claims)),
+        };
+
+        Ok(Response::new(response.unwrap_or_default()))
+    }
+
+    async fn send_magic_link(
+        &self,
+        request: Request<SendMagicLinkRequest>,
+    ) -> Result<Response<SendMagicLinkResponse>, Status> {
+        let request: SendMagicLinkReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        // the account lookup and the email happen in the background so the
+        // response takes the same time whether or not the account exists
+        let nonce = generate_secret();
+        jobs::enqueue(
+            &self.state,
+            Job::SendMagicLink {
+                email: request.email,
+                nonce: hash_secret(&nonce),
+            },
+        )
+        .await
+        .map_err(AppError::Other)?;
+
+        Ok(Response::new(SendMagicLinkResponse { nonce }))
+    }
+
+    async fn redeem_magic_link(
+        &self,
+        request: Request<RedeemMagicLinkRequest>,
+    ) -> Result<Response<LoginResponse>, Status> {
+        let proof = Proof::from_request(&request, "/auth.AuthService/RedeemMagicLink");
+        let request: RedeemMagicLinkReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+        let jkt = self.thumbprint(proof).await?;
+
+        let key = magic_link_key(&hash_secret(&request.token));
+        let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
+        let value: Option<String> = redis::cmd("GET")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        let value = value.ok_or(AppError::Unauthorized(anyhow::anyhow!(
+            "sign in link is invalid or has expired"
+        )))?;
+        let link: MagicLink = serde_json::from_str(&value).map_err(AppError::from_generic_error)?;
+
+        if link.nonce != hash_secret(&request.nonce) {
+            return Err(AppError::Unauthorized(anyhow::anyhow!(
+                "sign in link must be opened on the device it was requested from"
+            ))
+            .into());
+        }
+
+        let user = database::user::get_by_id(&self.state.db, &link.user_id)
+            .await
+            .map_err(AppError::from_database_error)?;
+        require_current_password(&user)?;
+        self.verify_two_factor(&user, request.otp.clone()).await?;
+        let location = geo::locate(&*self.state.geoip, &request.ip_address).await;
+        self.assess_risk(&user, &location, request.otp.as_deref())
+            .await?;
+        let cap = session_cap(
+            user.max_sessions,
+            ENV.max_sessions,
+            ENV.session_limit_action,
+        );
+        if let Some(cap) = cap.filter(|cap| cap.action == SessionLimitAction::Reject) {
+            // issuing enforces the cap, checking up front as well keeps a
+            // rejected login from burning the link
+            let live = self
+                .state
+                .store
+                .list_by_user(&user.id)
+                .await
+                .map_err(AppError::from_token_error)?;
+            if live.len() >= cap.limit {
+                return Err(AppError::Unauthorized(anyhow::anyhow!(
+                    "too many active sessions, sign out of another device first"
+                ))
+                .into());
+            }
+        }
+
+        // the link is only consumed once every check passed, so a second factor
+        // or a risk challenge can be completed with the same link
+        let consumed: Option<String> = redis::cmd("GETDEL")
+            .arg(&key)
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        if consumed.as_deref() != Some(value.as_str()) {
+            return Err(AppError::Unauthorized(anyhow::anyhow!(
+                "sign in link is invalid or has expired"
+            ))
+            .into());
+        }
+
+        let response = self
+            .issue(
+                user,
+                request.audience,
+                jkt,
+                request.remember_me.unwrap_or(true),
+                location,
+                request.user_agent,
+            )
+            .await?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_sessions(
+        &self,
+        request: Request<ListSessionsRequest>,
+    ) -> Result<Response<ListSessionsResponse>, Status> {
+        let proof = Proof::from_request(&request, "/auth.AuthService/ListSessions");
+        let request: ListSessionsReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        let claims = Access::default(self.state.clone())
+            .verify(
+                &request.access_token,
+                TokenType::Access,
+                Audience::Registered,
+                proof.as_ref(),
+            )
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        // rows of revoked or idle expired sessions linger until the sweeper
+        // removes them, the token store knows which ones are still live
+        let live = self
+            .state
+            .store
+            .list_by_user(claims.sub())
+            .await
+            .map_err(AppError::from_token_error)?;
+        let (sessions, total) = database::session::list(
+            &self.state.db,
+            claims.sub(),
+            &live,
+            request.page,
+            request.page_size,
+        )
+        .await
+        .map_err(AppError::from_database_error)?;
+
+        Ok(Response::new(ListSessionsResponse {
+            sessions: sessions
+                .into_iter()
+                .map(|session| SessionDetails::from_model(session, &claims.rjti))
+                .collect(),
+            page: request.page,
+            page_size: request.page_size,
+            total,
+        }))
+    }
+
+    async fn revoke_session(
+        &self,
+        request: Request<RevokeSessionRequest>,
+    ) -> Result<Response<RevokeSessionResponse>, Status> {
+        let proof = Proof::from_request(&request, "/auth.AuthService/RevokeSession");
+        let request: RevokeSessionReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        let claims = Access::default(self.state.clone())
+            .verify(
+                &request.access_token,
+                TokenType::Access,
+                Audience::Registered,
+                proof.as_ref(),
+            )
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        let session =
+            database::session::get_by_user(&self.state.db, &request.session_id, claims.sub())
+                .await
+                .map_err(AppError::from_database_error)?;
+
+        // the session is gone either way, its refresh token may simply have
+        // expired or been evicted already
+        Refresh::default(self.state.clone())
+            .end(&session.id)
+            .await
+            .map_err(AppError::from_token_error)?;
+
+        Ok(Response::new(RevokeSessionResponse {}))
+    }
+
+    async fn report_session(
+        &self,
+        request: Request<ReportSessionRequest>,
+    ) -> Result<Response<ReportSessionResponse>, Status> {
+        let request: ReportSessionReq = request.into_inner().into();
+        request
+            .validate()
+            .map_err(AppError::from_validation_errors)?;
+
+        let mut conn = self.state.get_redis_conn().await.map_err(AppError::Other)?;
+        let value: Option<String> = redis::cmd("GETDEL")
+            .arg(session_report_key(&hash_secret(&request.token)))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| AppError::Other(err.into()))?;
+        let report: SessionReport = serde_json::from_str(&value.ok_or(AppError::Unauthorized(
+            anyhow::anyhow!("link is invalid or has expired"),
+        ))?)
+        .map_err(AppError::from_generic_error)?;
+
+        // whoever signed in may know the password, so every token is revoked
+        // along with the reported session and a local password has to be reset
+        let user = database::user::get_by_id(&self.state.db, &report.user_id)
+            .await
+            .map_err(AppError::from_database_error)?;
+        if must_reset_password(&user) {
+            database::user::set_password_reset_required(&self.state.db, &user.id, true)
+                .await
+                .map_err(AppError::from_database_error)?;
+        }
+        epoch::revoke_all(&self.state, &report.user_id)
+            .await
+            .map_err(AppError::from_token_error)?;
+        if let Err(err) = Refresh::default(self.state.clone())
+            .delete(&report.rjti)
+            .await
+        {
+            // the session may already have been revoked or expired
+            log::warn!(
+                "{}",
+                anyhow::Error::new(err).context("failed to revoke the reported session")
+            )
+        }
+
+        Ok(Response::new(ReportSessionResponse {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{must_reset_password, require_current_password, session_cap, unrecorded_exp};
+    use crate::{config::env::SessionLimitAction, error::AppError, token::store::SessionCap};
+
+    fn user(password_reset_required: bool) -> entity::user::Model {
+        entity::user::Model {
+            id: String::from("01JQZ6V3W8Y2X4C5B7N9M1K3H5"),
+            email: String::from("jane@acme.com"),
+            username: String::from("jane"),
+            name: String::from("Jane Doe"),
+            password: None,
+            photo_url: None,
+            is_email_verified: true,
+            is_two_factor_enabled: false,
+            tokens_valid_after: 0,
+            profile_version: 0,
+            password_reset_required,
+            max_sessions: None,
+        }
+    }
+
+    #[test]
+    fn allows_users_with_a_current_password() {
+        assert!(require_current_password(&user(false)).is_ok());
+    }
+
+    #[test]
+    fn rejects_users_that_must_reset_their_password() {
+        let user = entity::user::Model {
+            password: Some(String::from("$2b$12$hash")),
+            ..user(true)
+        };
+        assert!(matches!(
+            require_current_password(&user),
+            Err(AppError::Unauthorized(_))
+        ));
+    }
+
+    #[test]
+    fn unrecorded_sessions_keep_their_remaining_lifetime() {
+        assert_eq!(unrecorded_exp(Some(600), 2_000), 2_600);
+        assert_eq!(unrecorded_exp(None, 2_000), 2_000);
+    }
+
+    #[test]
+    fn the_user_limit_overrides_the_global_one() {
+        let evict = SessionLimitAction::Evict;
+        assert_eq!(
+            session_cap(Some(3), 5, evict),
+            Some(SessionCap {
+                limit: 3,
+                action: evict
+            })
+        );
+        assert_eq!(
+            session_cap(None, 5, evict),
+            Some(SessionCap {
+                limit: 5,
+                action: evict
+            })
+        );
+        assert_eq!(session_cap(Some(0), 5, evict), None);
+        assert_eq!(session_cap(None, 0, evict), None);
+        assert_eq!(session_cap(Some(-1), 5, evict), None);
+    }
+
+    #[test]
+    fn reported_sessions_only_force_a_reset_of_local_passwords() {
+        let passwordless = user(false);
+        assert!(!must_reset_password(&passwordless));
+        assert!(require_current_password(&passwordless).is_ok());
+
+        let local = entity::user::Model {
+            password: Some(String::from("$2b$12$hash")),
+            ..user(false)
+        };
+        assert!(must_reset_password(&local));
+    }
+
+    #[test]
+    fn passwordless_accounts_are_never_locked_out() {
+        assert!(require_current_password(&user(true)).is_ok());
+    }
 }
@@ -59,3 +59,195 @@ pub fn send_otp(otp: &str, purpose: &str) -> Markup {
     }
     }
 }
+
+pub fn magic_link(link: &str, minutes: usize) -> Markup {
+    let validity = format!(
+        "Click the button below to sign in to your account. This link can only be used once and is valid for {} minutes.",
+        minutes
+    );
+
+    html! {
+    (DOCTYPE)
+    html {
+    head {
+    meta charset="UTF-8";
+    meta name="viewport" content="width=device-width, initial-scale=1.0";
+    title { "Sign in" }
+    }
+    body style="margin: 0; padding: 0; background-color: #f2f2f2;" {
+    table role="presentation" cellpadding="0" cellspacing="0" border="0" width="100%" {
+    tr {
+    td style="padding: 20px 0;" {
+    table align="center" cellpadding="0" cellspacing="0" border="0" width="600"
+    style="border-collapse: collapse; background-color: #ffffff; border-radius: 8px; overflow: hidden; box-shadow: 0 4px 10px rgba(0,0,0,0.15);"
+    {
+    tr {
+    td align="center" style="background-color: #2D89EF; padding: 30px 0;" {
+    h1 style="color: #ffffff; font-family: Arial, sans-serif; font-size: 28px; margin: 0;" { "Sign in" }
+    }
+    }
+    tr {
+    td style="padding: 40px 30px; font-family: Arial, sans-serif;" {
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" { "Hello," }
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" {
+    (validity.as_str())
+    }
+    table align="center" cellpadding="0" cellspacing="0" border="0" style="margin: 20px auto;" {
+    tr {
+    td style="background-color: #2D89EF; padding: 15px 25px; border-radius: 4px; text-align: center;" {
+    a href=(link) style="display: block; font-size: 18px; color: #ffffff; font-weight: bold; text-decoration: none;" { "Sign in to auth_rs" }
+    }
+    }
+    }
+    p style="color: #666666; font-size: 14px; margin: 20px 0 0;" {
+    "The link only works on the device you requested it from. If you did not request this email, please ignore it."
+    }
+    }
+    }
+    tr {
+    td style="background-color: #f7f7f7; padding: 20px 30px; text-align: center;" {
+    p style="color: #999999; font-size: 12px; margin: 0;" {
+    "© 2025 auth_rs. All rights reserved."
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+}
+
+pub fn password_reset(link: &str, minutes: usize) -> Markup {
+    let validity = format!(
+        "Click the button below to choose a new password. This link can only be used once and is valid for {} minutes.",
+        minutes
+    );
+
+    html! {
+    (DOCTYPE)
+    html {
+    head {
+    meta charset="UTF-8";
+    meta name="viewport" content="width=device-width, initial-scale=1.0";
+    title { "Reset your password" }
+    }
+    body style="margin: 0; padding: 0; background-color: #f2f2f2;" {
+    table role="presentation" cellpadding="0" cellspacing="0" border="0" width="100%" {
+    tr {
+    td style="padding: 20px 0;" {
+    table align="center" cellpadding="0" cellspacing="0" border="0" width="600"
+    style="border-collapse: collapse; background-color: #ffffff; border-radius: 8px; overflow: hidden; box-shadow: 0 4px 10px rgba(0,0,0,0.15);"
+    {
+    tr {
+    td align="center" style="background-color: #2D89EF; padding: 30px 0;" {
+    h1 style="color: #ffffff; font-family: Arial, sans-serif; font-size: 28px; margin: 0;" { "Reset your password" }
+    }
+    }
+    tr {
+    td style="padding: 40px 30px; font-family: Arial, sans-serif;" {
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" { "Hello," }
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" {
+    (validity.as_str())
+    }
+    table align="center" cellpadding="0" cellspacing="0" border="0" style="margin: 20px auto;" {
+    tr {
+    td style="background-color: #2D89EF; padding: 15px 25px; border-radius: 4px; text-align: center;" {
+    a href=(link) style="display: block; font-size: 18px; color: #ffffff; font-weight: bold; text-decoration: none;" { "Reset password" }
+    }
+    }
+    }
+    p style="color: #666666; font-size: 14px; margin: 20px 0 0;" {
+    "Resetting your password signs you out everywhere. If you did not request this email, please ignore it."
+    }
+    }
+    }
+    tr {
+    td style="background-color: #f7f7f7; padding: 20px 30px; text-align: center;" {
+    p style="color: #999999; font-size: 12px; margin: 0;" {
+    "© 2025 auth_rs. All rights reserved."
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+}
+
+pub fn new_device(device: &str, location: &str, time: &str, link: &str) -> Markup {
+    html! {
+    (DOCTYPE)
+    html {
+    head {
+    meta charset="UTF-8";
+    meta name="viewport" content="width=device-width, initial-scale=1.0";
+    title { "New sign in" }
+    }
+    body style="margin: 0; padding: 0; background-color: #f2f2f2;" {
+    table role="presentation" cellpadding="0" cellspacing="0" border="0" width="100%" {
+    tr {
+    td style="padding: 20px 0;" {
+    table align="center" cellpadding="0" cellspacing="0" border="0" width="600"
+    style="border-collapse: collapse; background-color: #ffffff; border-radius: 8px; overflow: hidden; box-shadow: 0 4px 10px rgba(0,0,0,0.15);"
+    {
+    tr {
+    td align="center" style="background-color: #2D89EF; padding: 30px 0;" {
+    h1 style="color: #ffffff; font-family: Arial, sans-serif; font-size: 28px; margin: 0;" { "New sign in" }
+    }
+    }
+    tr {
+    td style="padding: 40px 30px; font-family: Arial, sans-serif;" {
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" { "Hello," }
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" {
+    "Your account was just signed in to from a device or location we have not seen before."
+    }
+    table cellpadding="0" cellspacing="0" border="0" style="margin: 20px 0; font-size: 16px; color: #333333;" {
+    tr {
+    td style="padding: 5px 20px 5px 0; color: #666666;" { "Device" }
+    td style="padding: 5px 0;" { (device) }
+    }
+    tr {
+    td style="padding: 5px 20px 5px 0; color: #666666;" { "Location" }
+    td style="padding: 5px 0;" { (location) }
+    }
+    tr {
+    td style="padding: 5px 20px 5px 0; color: #666666;" { "Time" }
+    td style="padding: 5px 0;" { (time) }
+    }
+    }
+    p style="color: #333333; font-size: 16px; margin: 0 0 20px;" {
+    "If this was you, you can ignore this email. If it was not, sign the device out and secure your account."
+    }
+    table align="center" cellpadding="0" cellspacing="0" border="0" style="margin: 20px auto;" {
+    tr {
+    td style="background-color: #D9534F; padding: 15px 25px; border-radius: 4px; text-align: center;" {
+    a href=(link) style="display: block; font-size: 18px; color: #ffffff; font-weight: bold; text-decoration: none;" { "This wasn't me" }
+    }
+    }
+    }
+    p style="color: #666666; font-size: 14px; margin: 20px 0 0;" {
+    "Reporting the sign in signs the device out and requires a password reset before you can sign in again."
+    }
+    }
+    }
+    tr {
+    td style="background-color: #f7f7f7; padding: 20px 30px; text-align: center;" {
+    p style="color: #999999; font-size: 12px; margin: 0;" {
+    "© 2025 auth_rs. All rights reserved."
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+    }
+}
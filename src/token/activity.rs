@@ -0,0 +1,54 @@
+use super::error::TokenError;
+use crate::{
+    config::{ENV, state::AppState},
+    database,
+};
+
+/// minimum number of seconds between two activity writes for the same session
+const WRITE_INTERVAL: usize = 60;
+
+fn pending_key(rjti: &str) -> String {
+    format!("{}:session_activity:{}", &*ENV.redis_schema, rjti)
+}
+
+fn throttle_key(rjti: &str) -> String {
+    format!("{}:session_activity_throttle:{}", &*ENV.redis_schema, rjti)
+}
+
+/// records a refresh of the session, refreshes are counted in redis and only
+/// written to postgres once per interval so hot clients stay cheap
+pub async fn record(
+    state: &AppState,
+    rjti: &str,
+    ip_address: Option<&str>,
+) -> Result<(), TokenError> {
+    let mut conn = state.get_redis_conn().await.map_err(TokenError::Other)?;
+    let (_, _, acquired): (i64, bool, Option<String>) = redis::pipe()
+        .cmd("INCR")
+        .arg(pending_key(rjti))
+        .cmd("EXPIRE")
+        .arg(pending_key(rjti))
+        .arg(ENV.refresh_token_expiration)
+        .cmd("SET")
+        .arg(throttle_key(rjti))
+        .arg(1)
+        .arg("NX")
+        .arg("EX")
+        .arg(WRITE_INTERVAL)
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| TokenError::Other(err.into()))?;
+    if acquired.is_none() {
+        return Ok(());
+    }
+
+    // refreshes counted after this point are flushed by the next write
+    let refreshes: Option<i32> = redis::cmd("GETDEL")
+        .arg(pending_key(rjti))
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| TokenError::Other(err.into()))?;
+    database::session::touch(&state.db, rjti, ip_address, refreshes.unwrap_or_default())
+        .await
+        .map_err(|err| TokenError::Other(err.into()))
+}
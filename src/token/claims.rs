@@ -1,4 +1,9 @@
-use crate::{model::user::UserDetails, util::now};
+use super::dpop::Confirmation;
+use crate::{
+    config::ENV,
+    model::{role::Grants, user::UserDetails},
+    util::now,
+};
 use serde::{Deserialize, Serialize};
 
 pub trait Claims {
@@ -9,6 +14,29 @@ pub trait Claims {
     fn exp(&self) -> usize;
     fn nbf(&self) -> usize;
     fn custom(&self) -> Option<&str>;
+    fn iss(&self) -> &str;
+    fn aud(&self) -> &str;
+    fn scope(&self) -> Option<&str>;
+    fn roles(&self) -> Option<&[String]>;
+    fn act(&self) -> Option<&Actor>;
+    fn cnf(&self) -> Option<&Confirmation>;
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Actor {
+    pub sub: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Box<Actor>>,
+}
+
+impl Actor {
+    pub fn new(sub: String, prior: Option<Actor>) -> Self {
+        Self {
+            sub,
+            act: prior.map(Box::new),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -20,6 +48,21 @@ pub struct PrimaryClaims {
     pub iat: usize,
     pub nbf: usize,
     pub custom: Option<String>,
+
+    pub iss: String,
+    pub aud: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub roles: Option<Vec<String>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cnf: Option<Confirmation>,
 }
 
 impl PrimaryClaims {
@@ -42,8 +85,49 @@ impl PrimaryClaims {
             iat: now,
             nbf: now,
             custom,
+            iss: ENV.token_issuer.to_string(),
+            aud: ENV.token_audiences[0].clone(),
+            scope: None,
+            roles: None,
+            act: None,
+            cnf: None,
         }
     }
+
+    pub fn with_grants(mut self, grants: Option<&Grants>) -> Self {
+        if let Some(grants) = grants {
+            self.roles = (!grants.roles.is_empty()).then(|| grants.roles.clone());
+            self.scope = (!grants.permissions.is_empty()).then(|| grants.scope());
+        }
+        self
+    }
+
+    pub fn with_roles(mut self, roles: Option<Vec<String>>) -> Self {
+        self.roles = roles;
+        self
+    }
+
+    pub fn with_aud(mut self, aud: Option<String>) -> Self {
+        if let Some(aud) = aud {
+            self.aud = aud;
+        }
+        self
+    }
+
+    pub fn with_scope(mut self, scope: Option<String>) -> Self {
+        self.scope = scope;
+        self
+    }
+
+    pub fn with_act(mut self, act: Option<Actor>) -> Self {
+        self.act = act;
+        self
+    }
+
+    pub fn with_cnf(mut self, jkt: Option<String>) -> Self {
+        self.cnf = jkt.map(|jkt| Confirmation { jkt });
+        self
+    }
 }
 
 impl Claims for PrimaryClaims {
@@ -74,6 +158,30 @@ impl Claims for PrimaryClaims {
     fn custom(&self) -> Option<&str> {
         self.custom.as_deref()
     }
+
+    fn iss(&self) -> &str {
+        &self.iss
+    }
+
+    fn aud(&self) -> &str {
+        &self.aud
+    }
+
+    fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    fn roles(&self) -> Option<&[String]> {
+        self.roles.as_deref()
+    }
+
+    fn act(&self) -> Option<&Actor> {
+        self.act.as_ref()
+    }
+
+    fn cnf(&self) -> Option<&Confirmation> {
+        self.cnf.as_ref()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -86,9 +194,16 @@ pub struct ExtendedClaims {
 }
 
 impl ExtendedClaims {
-    pub fn new(user: &UserDetails, exp: usize) -> Self {
+    pub fn new(
+        user: &UserDetails,
+        exp: usize,
+        aud: Option<String>,
+        grants: Option<&Grants>,
+    ) -> Self {
         Self {
-            primary: PrimaryClaims::new(user.id.clone(), exp, None, None, None),
+            primary: PrimaryClaims::new(user.id.clone(), exp, None, None, None)
+                .with_aud(aud)
+                .with_grants(grants),
             user: user.into(),
         }
     }
@@ -122,4 +237,70 @@ impl Claims for ExtendedClaims {
     fn custom(&self) -> Option<&str> {
         None
     }
+
+    fn iss(&self) -> &str {
+        self.primary.iss()
+    }
+
+    fn aud(&self) -> &str {
+        self.primary.aud()
+    }
+
+    fn scope(&self) -> Option<&str> {
+        self.primary.scope()
+    }
+
+    fn roles(&self) -> Option<&[String]> {
+        self.primary.roles()
+    }
+
+    fn act(&self) -> Option<&Actor> {
+        self.primary.act()
+    }
+
+    fn cnf(&self) -> Option<&Confirmation> {
+        self.primary.cnf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PrimaryClaims;
+    use crate::model::role::Grants;
+
+    fn claims() -> PrimaryClaims {
+        PrimaryClaims {
+            sub: String::from("sub"),
+            jti: String::from("jti"),
+            rjti: String::from("jti"),
+            exp: 0,
+            iat: 0,
+            nbf: 0,
+            custom: None,
+            iss: String::from("iss"),
+            aud: String::from("aud"),
+            scope: None,
+            roles: None,
+            act: None,
+            cnf: None,
+        }
+    }
+
+    #[test]
+    fn with_grants_omits_empty_grants() {
+        let claims = claims().with_grants(Some(&Grants::default()));
+        assert_eq!(claims.scope, None);
+        assert_eq!(claims.roles, None);
+    }
+
+    #[test]
+    fn with_grants_sets_roles_and_scope() {
+        let grants = Grants {
+            roles: vec![String::from("editor")],
+            permissions: vec![String::from("read"), String::from("write")],
+        };
+        let claims = claims().with_grants(Some(&grants));
+        assert_eq!(claims.scope.as_deref(), Some("read write"));
+        assert_eq!(claims.roles, Some(vec![String::from("editor")]));
+    }
 }
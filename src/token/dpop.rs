@@ -0,0 +1,351 @@
+use super::error::TokenError;
+use crate::{
+    config::{ENV, state::AppState},
+    util::{hash_secret, now},
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, Validation,
+    jwk::{AlgorithmParameters, Jwk},
+};
+use serde::{Deserialize, Serialize};
+use tonic::{Request, metadata::MetadataMap};
+
+const PROOF_TYPE: &str = "dpop+jwt";
+const PROOF_MAX_AGE: usize = 60;
+const PROOF_CLOCK_SKEW: usize = 5;
+
+const ALLOWED_ALGORITHMS: [Algorithm; 6] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::PS256,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+#[derive(Debug, Deserialize)]
+struct ProofClaims {
+    jti: String,
+    htm: String,
+    htu: String,
+    iat: usize,
+    ath: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Confirmation {
+    pub jkt: String,
+}
+
+/// a dpop proof presented with a request, along with the method and target
+/// it was made for
+#[derive(Debug, Clone)]
+pub struct Proof {
+    pub proof: String,
+    pub htm: &'static str,
+    pub path: &'static str,
+}
+
+impl Proof {
+    /// grpc calls are always POST requests to the fully qualified method path
+    pub fn from_request<T>(request: &Request<T>, path: &'static str) -> Option<Self> {
+        Self::from_metadata(request.metadata(), path)
+    }
+
+    pub fn from_metadata(metadata: &MetadataMap, path: &'static str) -> Option<Self> {
+        let proof = metadata.get("dpop")?.to_str().ok()?;
+
+        Some(Self {
+            proof: proof.to_owned(),
+            htm: "POST",
+            path,
+        })
+    }
+
+    fn matches_htu(&self, origin: Option<&str>, htu: &str) -> bool {
+        let htu = htu.split(['?', '#']).next().unwrap_or_default();
+
+        match origin {
+            Some(origin) => htu == format!("{}{}", origin.trim_end_matches('/'), self.path),
+            None => htu
+                .split_once("://")
+                .and_then(|(_, rest)| rest.find('/').map(|i| &rest[i..]))
+                .is_some_and(|path| path == self.path),
+        }
+    }
+
+    /// validates the proof and returns the thumbprint of its key, the proof must
+    /// carry the hash of `access_token` when one is given
+    pub async fn verify(
+        &self,
+        state: &AppState,
+        access_token: Option<&str>,
+    ) -> Result<String, TokenError> {
+        let (jti, jkt) = self.validate(ENV.dpop_origin.as_deref(), access_token, now())?;
+
+        let mut conn = state.get_redis_conn().await.map_err(TokenError::Other)?;
+        let fresh: bool = redis::cmd("SET")
+            .arg(format!("{}:dpop:{}", &*ENV.redis_schema, jti))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(PROOF_MAX_AGE + PROOF_CLOCK_SKEW)
+            .query_async::<Option<String>>(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))?
+            .is_some();
+        if !fresh {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "dpop proof has already been used"
+            )));
+        }
+
+        Ok(jkt)
+    }
+
+    /// checks everything but replay, returns the jti and key thumbprint
+    fn validate(
+        &self,
+        origin: Option<&str>,
+        access_token: Option<&str>,
+        now: usize,
+    ) -> Result<(String, String), TokenError> {
+        let header = jsonwebtoken::decode_header(&self.proof)
+            .map_err(|err| TokenError::Parsing(err.into()))?;
+        if header.typ.as_deref() != Some(PROOF_TYPE) {
+            return Err(TokenError::InvalidFormat(anyhow::anyhow!(
+                "dpop proof must be of type {}",
+                PROOF_TYPE
+            )));
+        }
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(TokenError::InvalidFormat(anyhow::anyhow!(
+                "dpop proof algorithm is not supported"
+            )));
+        }
+        let jwk = header.jwk.ok_or_else(|| {
+            TokenError::MissingClaims(anyhow::anyhow!("dpop proof is missing the jwk header"))
+        })?;
+
+        let key = DecodingKey::from_jwk(&jwk).map_err(|err| TokenError::Parsing(err.into()))?;
+        let mut validation = Validation::new(header.alg);
+        validation.validate_exp = false;
+        validation.validate_aud = false;
+        validation.set_required_spec_claims::<&str>(&[]);
+        let claims = jsonwebtoken::decode::<ProofClaims>(&self.proof, &key, &validation)
+            .map_err(|err| TokenError::Validation(err.into()))?
+            .claims;
+
+        if claims.htm != self.htm || !self.matches_htu(origin, &claims.htu) {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "dpop proof was not made for this request"
+            )));
+        }
+
+        if now.saturating_sub(claims.iat) > PROOF_MAX_AGE
+            || claims.iat > now.saturating_add(PROOF_CLOCK_SKEW)
+        {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "dpop proof has expired"
+            )));
+        }
+
+        if let Some(access_token) = access_token
+            && claims.ath.as_deref() != Some(hash_secret(access_token).as_str())
+        {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "dpop proof is not bound to the access token"
+            )));
+        }
+
+        Ok((claims.jti, thumbprint(&jwk)?))
+    }
+}
+
+/// jwk thumbprint as defined by rfc 7638
+pub fn thumbprint(jwk: &Jwk) -> Result<String, TokenError> {
+    let curve = |curve| {
+        serde_json::to_value(curve)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_owned))
+            .ok_or_else(|| TokenError::Parsing(anyhow::anyhow!("jwk curve is invalid")))
+    };
+
+    // members must be in lexicographic order without whitespace
+    let canonical = match &jwk.algorithm {
+        AlgorithmParameters::RSA(params) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, params.e, params.n)
+        }
+        AlgorithmParameters::EllipticCurve(params) => format!(
+            r#"{{"crv":"{}","kty":"EC","x":"{}","y":"{}"}}"#,
+            curve(&params.curve)?,
+            params.x,
+            params.y
+        ),
+        AlgorithmParameters::OctetKeyPair(params) => format!(
+            r#"{{"crv":"{}","kty":"OKP","x":"{}"}}"#,
+            curve(&params.curve)?,
+            params.x
+        ),
+        AlgorithmParameters::OctetKey(_) => {
+            return Err(TokenError::InvalidFormat(anyhow::anyhow!(
+                "dpop proof must use an asymmetric key"
+            )));
+        }
+    };
+
+    Ok(hash_secret(&canonical))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PROOF_MAX_AGE, Proof, thumbprint};
+    use crate::util::hash_secret;
+    use base64::prelude::*;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, jwk::Jwk};
+    use openssl::{
+        bn::BigNumContext,
+        ec::{EcGroup, EcKey},
+        nid::Nid,
+        pkey::PKey,
+    };
+    use serde_json::json;
+
+    const PATH: &str = "/auth.AuthService/Refresh";
+    const NOW: usize = 1_700_000_000;
+
+    struct Signer {
+        key: EncodingKey,
+        jwk: Jwk,
+    }
+
+    fn signer() -> Signer {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = EcKey::generate(&group).unwrap();
+        let (mut x, mut y) = (
+            openssl::bn::BigNum::new().unwrap(),
+            openssl::bn::BigNum::new().unwrap(),
+        );
+        ec.public_key()
+            .affine_coordinates(&group, &mut x, &mut y, &mut BigNumContext::new().unwrap())
+            .unwrap();
+        let jwk = serde_json::from_value(json!({
+            "kty": "EC",
+            "crv": "P-256",
+            "x": BASE64_URL_SAFE_NO_PAD.encode(x.to_vec_padded(32).unwrap()),
+            "y": BASE64_URL_SAFE_NO_PAD.encode(y.to_vec_padded(32).unwrap()),
+        }))
+        .unwrap();
+        let pem = PKey::from_ec_key(ec)
+            .unwrap()
+            .private_key_to_pem_pkcs8()
+            .unwrap();
+
+        Signer {
+            key: EncodingKey::from_ec_pem(&pem).unwrap(),
+            jwk,
+        }
+    }
+
+    impl Signer {
+        fn proof(&self, typ: &str, claims: serde_json::Value) -> Proof {
+            let mut header = Header::new(Algorithm::ES256);
+            header.typ = Some(typ.to_owned());
+            header.jwk = Some(self.jwk.clone());
+
+            Proof {
+                proof: jsonwebtoken::encode(&header, &claims, &self.key).unwrap(),
+                htm: "POST",
+                path: PATH,
+            }
+        }
+
+        fn claims(&self, iat: usize, ath: Option<&str>) -> serde_json::Value {
+            json!({
+                "jti": "proof",
+                "htm": "POST",
+                "htu": format!("https://auth.example.com{}", PATH),
+                "iat": iat,
+                "ath": ath,
+            })
+        }
+    }
+
+    #[test]
+    fn accepts_a_fresh_proof() {
+        let signer = signer();
+        let proof = signer.proof("dpop+jwt", signer.claims(NOW, None));
+
+        let (jti, jkt) = proof.validate(None, None, NOW).unwrap();
+        assert_eq!(jti, "proof");
+        assert_eq!(jkt, thumbprint(&signer.jwk).unwrap());
+    }
+
+    #[test]
+    fn rejects_a_proof_of_another_type() {
+        let signer = signer();
+        let proof = signer.proof("JWT", signer.claims(NOW, None));
+
+        assert!(proof.validate(None, None, NOW).is_err());
+    }
+
+    #[test]
+    fn rejects_a_proof_for_another_request() {
+        let signer = signer();
+        let mut claims = signer.claims(NOW, None);
+        claims["htu"] = json!("https://auth.example.com/auth.AuthService/Login");
+
+        assert!(
+            signer
+                .proof("dpop+jwt", claims)
+                .validate(None, None, NOW)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn checks_the_origin_when_configured() {
+        let signer = signer();
+        let proof = signer.proof("dpop+jwt", signer.claims(NOW, None));
+
+        assert!(
+            proof
+                .validate(Some("https://auth.example.com/"), None, NOW)
+                .is_ok()
+        );
+        assert!(
+            proof
+                .validate(Some("https://evil.example.com"), None, NOW)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_stale_and_future_proofs() {
+        let signer = signer();
+        let stale = signer.proof("dpop+jwt", signer.claims(NOW - PROOF_MAX_AGE - 1, None));
+        let future = signer.proof("dpop+jwt", signer.claims(NOW + 60, None));
+
+        assert!(stale.validate(None, None, NOW).is_err());
+        assert!(future.validate(None, None, NOW).is_err());
+    }
+
+    #[test]
+    fn rejects_extreme_timestamps_without_overflowing() {
+        let signer = signer();
+        let proof = signer.proof("dpop+jwt", signer.claims(usize::MAX, None));
+
+        assert!(proof.validate(None, None, NOW).is_err());
+        assert!(proof.validate(None, None, usize::MAX).is_ok());
+    }
+
+    #[test]
+    fn binds_the_proof_to_the_access_token() {
+        let signer = signer();
+        let ath = hash_secret("access-token");
+        let proof = signer.proof("dpop+jwt", signer.claims(NOW, Some(&ath)));
+
+        assert!(proof.validate(None, Some("access-token"), NOW).is_ok());
+        assert!(proof.validate(None, Some("other-token"), NOW).is_err());
+    }
+}
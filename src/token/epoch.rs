@@ -0,0 +1,131 @@
+//! per-user token epochs, every token issued before the epoch of its subject
+//! is revoked
+//!
+//! epochs are cached in redis directly rather than through the token store,
+//! so checking them still needs redis when `TOKEN_STORE=memory`
+
+use super::error::TokenError;
+use crate::{
+    config::{ENV, state::AppState},
+    database,
+};
+use sea_orm::DbErr;
+use std::time::SystemTime;
+use ulid::Ulid;
+
+fn key(user_id: &str) -> String {
+    format!("{}:token_epoch_ms:{}", &*ENV.redis_schema, user_id)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn map_err(err: DbErr) -> TokenError {
+    match err {
+        DbErr::RecordNotFound(_) => {
+            TokenError::Validation(anyhow::anyhow!("token subject no longer exists"))
+        }
+        err => TokenError::Other(err.into()),
+    }
+}
+
+/// returns the time in milliseconds before which every token issued to the
+/// user is invalid, postgres is the source of truth and redis only caches it
+pub async fn get(state: &AppState, user_id: &str) -> Result<u64, TokenError> {
+    let mut conn = state.get_redis_conn().await.map_err(TokenError::Other)?;
+    let cached: Option<u64> = redis::cmd("GET")
+        .arg(key(user_id))
+        .query_async(&mut conn)
+        .await
+        .map_err(|err| TokenError::Other(err.into()))?;
+    if let Some(epoch) = cached {
+        return Ok(epoch);
+    }
+
+    let epoch = database::user::get_tokens_valid_after(&state.db, user_id)
+        .await
+        .map_err(map_err)?;
+    let epoch = u64::try_from(epoch).unwrap_or_default();
+    cache(state, user_id, epoch).await?;
+
+    Ok(epoch)
+}
+
+/// invalidates every access, refresh, session and reauth token issued to the
+/// user until now, returns the new epoch in milliseconds
+pub async fn revoke_all(state: &AppState, user_id: &str) -> Result<u64, TokenError> {
+    let epoch = now_ms();
+    database::user::set_tokens_valid_after(
+        &state.db,
+        user_id,
+        epoch.try_into().map_err(|_| {
+            TokenError::Other(anyhow::anyhow!("failed to convert the token epoch to i64"))
+        })?,
+    )
+    .await
+    .map_err(map_err)?;
+    cache(state, user_id, epoch).await?;
+
+    Ok(epoch)
+}
+
+/// when a token was issued in milliseconds, `iat` only has second precision
+/// so it is read from the ulid jti and `iat` is only the fallback
+pub fn issued_at(jti: &str, iat: usize) -> u64 {
+    Ulid::from_string(jti)
+        .map(|jti| jti.timestamp_ms())
+        .unwrap_or(iat as u64 * 1000)
+}
+
+/// a token issued right after the revocation, even within the same second,
+/// stays valid
+pub fn revokes(epoch: u64, issued_at: u64) -> bool {
+    issued_at < epoch
+}
+
+async fn cache(state: &AppState, user_id: &str, epoch: u64) -> Result<(), TokenError> {
+    let mut conn = state.get_redis_conn().await.map_err(TokenError::Other)?;
+    redis::cmd("SET")
+        .arg(key(user_id))
+        .arg(epoch)
+        .arg("EX")
+        .arg(ENV.refresh_token_expiration)
+        .query_async::<()>(&mut conn)
+        .await
+        .map_err(|err| TokenError::Other(err.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{issued_at, revokes};
+    use ulid::Ulid;
+
+    #[test]
+    fn revokes_tokens_issued_before_the_epoch() {
+        assert!(revokes(1_700_000_000_500, 1_700_000_000_499));
+        assert!(!revokes(1_700_000_000_500, 1_700_000_000_500));
+        assert!(!revokes(1_700_000_000_500, 1_700_000_000_501));
+    }
+
+    #[test]
+    fn keeps_tokens_issued_in_the_same_second_after_the_epoch() {
+        let epoch = 1_700_000_000_200;
+        let jti = Ulid::from_parts(1_700_000_000_700, 42).to_string();
+
+        assert!(!revokes(epoch, issued_at(&jti, 1_700_000_000)));
+    }
+
+    #[test]
+    fn falls_back_to_iat_for_jtis_that_are_not_ulids() {
+        assert_eq!(issued_at("jti", 1_700_000_000), 1_700_000_000_000);
+    }
+
+    #[test]
+    fn keeps_every_token_without_an_epoch() {
+        assert!(!revokes(0, 1_700_000_000_000));
+    }
+}
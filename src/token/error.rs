@@ -15,6 +15,9 @@ pub enum TokenError {
     #[error("[missing_claims] {0}")]
     MissingClaims(#[source] anyhow::Error),
 
+    #[error("[session_limit] {0}")]
+    SessionLimit(#[source] anyhow::Error),
+
     #[error("transparent")]
     Other(#[source] anyhow::Error),
 }
@@ -0,0 +1,142 @@
+use super::TokenType;
+use crate::config::env::{Env, TokenFormat};
+use anyhow::Context;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use openssl::pkey::{Id, PKey, Private, Public};
+use std::fmt;
+
+/// rsa keys used to sign and verify one token type as a jwt
+pub struct KeyPair {
+    pub encoding: EncodingKey,
+    pub decoding: DecodingKey,
+}
+
+impl KeyPair {
+    pub fn from_rsa_pem(private_key: &[u8], public_key: &[u8]) -> Result<Self, anyhow::Error> {
+        let pair = AsymmetricKeys::from_pem(private_key, public_key)?;
+        if pair.public.id() != Id::RSA {
+            anyhow::bail!("key pair must be an RSA key pair");
+        }
+
+        Ok(Self {
+            encoding: EncodingKey::from_rsa_pem(private_key)?,
+            decoding: DecodingKey::from_rsa_pem(public_key)?,
+        })
+    }
+}
+
+pub struct AsymmetricKeys {
+    pub private: PKey<Private>,
+    pub public: PKey<Public>,
+}
+
+impl AsymmetricKeys {
+    pub fn from_pem(private_key: &[u8], public_key: &[u8]) -> Result<Self, anyhow::Error> {
+        let private = PKey::private_key_from_pem(private_key).context("invalid private key")?;
+        let public = PKey::public_key_from_pem(public_key).context("invalid public key")?;
+        if !public.public_eq(&private) {
+            anyhow::bail!("public key does not belong to the private key");
+        }
+
+        Ok(Self { private, public })
+    }
+}
+
+/// every key the service signs, verifies or encrypts tokens with, parsed once
+/// at boot
+pub struct Keys {
+    pub access: KeyPair,
+    pub refresh: KeyPair,
+    pub session: KeyPair,
+    pub reauth: KeyPair,
+    pub paseto: Option<AsymmetricKeys>,
+    pub session_encryption: Option<AsymmetricKeys>,
+}
+
+// key material stays out of logs
+impl fmt::Debug for Keys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keys").finish_non_exhaustive()
+    }
+}
+
+impl Keys {
+    pub fn new(env: &Env) -> Result<Self, anyhow::Error> {
+        let paseto = match (&env.paseto_private_key, &env.paseto_public_key) {
+            (Some(private_key), Some(public_key)) => {
+                let keys = AsymmetricKeys::from_pem(private_key, public_key)
+                    .context("PASETO_PRIVATE_KEY and PASETO_PUBLIC_KEY")?;
+                if keys.public.id() != Id::ED25519 {
+                    anyhow::bail!("PASETO_PRIVATE_KEY and PASETO_PUBLIC_KEY must be Ed25519 keys");
+                }
+                Some(keys)
+            }
+            (None, None) => None,
+            _ => anyhow::bail!("PASETO_PRIVATE_KEY and PASETO_PUBLIC_KEY must be set together"),
+        };
+        let formats = [
+            env.access_token_format,
+            env.refresh_token_format,
+            env.session_token_format,
+            env.reauth_token_format,
+        ];
+        if paseto.is_none() && formats.contains(&TokenFormat::Paseto) {
+            anyhow::bail!(
+                "PASETO_PRIVATE_KEY and PASETO_PUBLIC_KEY are required for paseto tokens"
+            );
+        }
+
+        let session_encryption = match (
+            &env.session_token_encryption_private_key,
+            &env.session_token_encryption_public_key,
+        ) {
+            (Some(private_key), Some(public_key)) => {
+                let keys = AsymmetricKeys::from_pem(private_key, public_key).context(
+                    "SESSION_TOKEN_ENCRYPTION_PRIVATE_KEY and SESSION_TOKEN_ENCRYPTION_PUBLIC_KEY",
+                )?;
+                if !matches!(keys.public.id(), Id::RSA | Id::EC) {
+                    anyhow::bail!("session token encryption keys must be RSA or EC keys");
+                }
+                Some(keys)
+            }
+            (None, None) => None,
+            _ => anyhow::bail!(
+                "SESSION_TOKEN_ENCRYPTION_PRIVATE_KEY and SESSION_TOKEN_ENCRYPTION_PUBLIC_KEY must be set together"
+            ),
+        };
+
+        Ok(Self {
+            access: KeyPair::from_rsa_pem(
+                &env.access_token_private_key,
+                &env.access_token_public_key,
+            )
+            .context("ACCESS_TOKEN_PRIVATE_KEY and ACCESS_TOKEN_PUBLIC_KEY")?,
+            refresh: KeyPair::from_rsa_pem(
+                &env.refresh_token_private_key,
+                &env.refresh_token_public_key,
+            )
+            .context("REFRESH_TOKEN_PRIVATE_KEY and REFRESH_TOKEN_PUBLIC_KEY")?,
+            session: KeyPair::from_rsa_pem(
+                &env.session_token_private_key,
+                &env.session_token_public_key,
+            )
+            .context("SESSION_TOKEN_PRIVATE_KEY and SESSION_TOKEN_PUBLIC_KEY")?,
+            reauth: KeyPair::from_rsa_pem(
+                &env.reauth_token_private_key,
+                &env.reauth_token_public_key,
+            )
+            .context("REAUTH_TOKEN_PRIVATE_KEY and REAUTH_TOKEN_PUBLIC_KEY")?,
+            paseto,
+            session_encryption,
+        })
+    }
+
+    pub fn get(&self, token_type: TokenType) -> &KeyPair {
+        match token_type {
+            TokenType::Access => &self.access,
+            TokenType::Refresh => &self.refresh,
+            TokenType::Session => &self.session,
+            TokenType::ReAuth => &self.reauth,
+        }
+    }
+}
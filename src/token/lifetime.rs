@@ -0,0 +1,21 @@
+use crate::config::ENV;
+
+/// sliding window of a session, remember me sessions get the long lifetime
+/// while browser sessions get the short one
+pub fn window(remember_me: bool) -> usize {
+    match remember_me {
+        true => ENV.refresh_token_expiration,
+        false => ENV.short_refresh_token_expiration,
+    }
+}
+
+/// when a session refreshed at `now` expires, the sliding window and the idle
+/// timeout restart on every refresh but never extend past the absolute cap
+pub fn expires_at(remember_me: bool, login_at: usize, now: usize) -> usize {
+    let mut exp = now + window(remember_me);
+    if ENV.session_idle_timeout > 0 {
+        exp = exp.min(now + ENV.session_idle_timeout);
+    }
+
+    exp.min(login_at + ENV.session_absolute_lifetime)
+}
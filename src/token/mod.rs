@@ -1,14 +1,42 @@
 use crate::config::ENV;
 use std::fmt::{Display, Formatter, Result};
 
+pub mod activity;
 pub mod claims;
+pub mod dpop;
+pub mod epoch;
 pub mod error;
+pub mod jwe;
+pub mod keys;
+pub mod lifetime;
 pub mod params;
+pub mod paseto;
 pub mod response;
 pub mod service;
+pub mod store;
 pub mod traits;
 pub mod types;
 
+/// the audience a token is verified for, there is no default so a token
+/// issued for one service is never accepted for another by accident
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience<'a> {
+    /// the token must have been issued for exactly this audience
+    Exact(&'a str),
+    /// any audience in TOKEN_AUDIENCES, for tokens presented back to this service
+    Registered,
+}
+
+impl Audience<'_> {
+    pub fn accepts(&self, aud: &str) -> bool {
+        match self {
+            Self::Exact(audience) => *audience == aud,
+            Self::Registered => ENV.token_audiences.iter().any(|audience| audience == aud),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenType {
     Access,
     Refresh,
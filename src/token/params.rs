@@ -1,7 +1,14 @@
+use crate::{model::role::Grants, token::store::SessionCap};
+
 #[derive(Debug, Default)]
 pub struct TokenParams {
     pub ajti: Option<String>,
     pub rjti: Option<String>,
+    pub aud: Option<String>,
+    pub grants: Option<Grants>,
+    pub jkt: Option<String>,
+    pub remember_me: Option<bool>,
+    pub session_cap: Option<SessionCap>,
 }
 
 impl TokenParams {
@@ -14,4 +21,29 @@ impl TokenParams {
         self.rjti = Some(rjti);
         self
     }
+
+    pub fn with_aud(mut self, aud: String) -> Self {
+        self.aud = Some(aud);
+        self
+    }
+
+    pub fn with_grants(mut self, grants: Grants) -> Self {
+        self.grants = Some(grants);
+        self
+    }
+
+    pub fn with_jkt(mut self, jkt: Option<String>) -> Self {
+        self.jkt = jkt;
+        self
+    }
+
+    pub fn with_remember_me(mut self, remember_me: bool) -> Self {
+        self.remember_me = Some(remember_me);
+        self
+    }
+
+    pub fn with_session_cap(mut self, session_cap: Option<SessionCap>) -> Self {
+        self.session_cap = session_cap;
+        self
+    }
 }
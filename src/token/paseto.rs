@@ -0,0 +1,280 @@
+use super::{TokenType, claims::Claims, error::TokenError, keys::Keys};
+use crate::util::now;
+use base64::prelude::*;
+use jsonwebtoken::Validation;
+use openssl::sign::{Signer, Verifier};
+use serde::{Deserialize, Serialize};
+
+const HEADER: &str = "v4.public.";
+const SIGNATURE_LEN: usize = 64;
+
+pub fn is_paseto(token: &str) -> bool {
+    token.starts_with(HEADER)
+}
+
+/// pre-authentication encoding, every piece is prefixed with its little endian
+/// length so that no two inputs can produce the same signed message
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    let le64 = |n: usize| ((n as u64) & (u64::MAX >> 1)).to_le_bytes();
+
+    let mut out = le64(pieces.len()).to_vec();
+    for piece in pieces {
+        out.extend_from_slice(&le64(piece.len()));
+        out.extend_from_slice(piece);
+    }
+    out
+}
+
+/// the token type is bound as the implicit assertion, a paseto issued as one
+/// token type never verifies as another even though they share a key pair
+fn implicit(token_type: TokenType) -> String {
+    token_type.to_string()
+}
+
+pub fn sign<T: Serialize>(
+    claims: &T,
+    token_type: TokenType,
+    keys: &Keys,
+) -> Result<String, TokenError> {
+    let key = &keys
+        .paseto
+        .as_ref()
+        .ok_or_else(|| {
+            TokenError::Creation(anyhow::anyhow!("PASETO_PRIVATE_KEY is not configured"))
+        })?
+        .private;
+
+    let message = serde_json::to_vec(claims).map_err(|err| TokenError::Creation(err.into()))?;
+    let signature = Signer::new_without_digest(key)
+        .and_then(|mut signer| {
+            signer.sign_oneshot_to_vec(&pae(&[
+                HEADER.as_bytes(),
+                &message,
+                b"",
+                implicit(token_type).as_bytes(),
+            ]))
+        })
+        .map_err(|err| TokenError::Creation(err.into()))?;
+
+    Ok(format!(
+        "{}{}",
+        HEADER,
+        BASE64_URL_SAFE_NO_PAD.encode([message, signature].concat())
+    ))
+}
+
+pub fn verify<T>(
+    token: &str,
+    token_type: TokenType,
+    validation: &Validation,
+    keys: &Keys,
+) -> Result<T, TokenError>
+where
+    T: for<'a> Deserialize<'a>,
+    T: Claims,
+{
+    let key = &keys
+        .paseto
+        .as_ref()
+        .ok_or_else(|| {
+            TokenError::Validation(anyhow::anyhow!("PASETO_PUBLIC_KEY is not configured"))
+        })?
+        .public;
+
+    let body = token.strip_prefix(HEADER).ok_or_else(|| {
+        TokenError::InvalidFormat(anyhow::anyhow!("token is not a v4.public paseto"))
+    })?;
+    let (payload, footer) = match body.split_once('.') {
+        Some((payload, footer)) => (
+            payload,
+            BASE64_URL_SAFE_NO_PAD
+                .decode(footer)
+                .map_err(|err| TokenError::InvalidFormat(err.into()))?,
+        ),
+        None => (body, vec![]),
+    };
+
+    let payload = BASE64_URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|err| TokenError::InvalidFormat(err.into()))?;
+    if payload.len() < SIGNATURE_LEN {
+        return Err(TokenError::InvalidFormat(anyhow::anyhow!(
+            "paseto payload is too short"
+        )));
+    }
+    let (message, signature) = payload.split_at(payload.len() - SIGNATURE_LEN);
+
+    let valid = Verifier::new_without_digest(key)
+        .and_then(|mut verifier| {
+            verifier.verify_oneshot(
+                signature,
+                &pae(&[
+                    HEADER.as_bytes(),
+                    message,
+                    &footer,
+                    implicit(token_type).as_bytes(),
+                ]),
+            )
+        })
+        .map_err(|err| TokenError::Validation(err.into()))?;
+    if !valid {
+        return Err(TokenError::Validation(anyhow::anyhow!(
+            "paseto signature is invalid"
+        )));
+    }
+
+    let claims: T =
+        serde_json::from_slice(message).map_err(|err| TokenError::Parsing(err.into()))?;
+    validate(&claims, validation)?;
+
+    Ok(claims)
+}
+
+/// applies the same registered claim checks jsonwebtoken performs for jwts
+fn validate<T: Claims>(claims: &T, validation: &Validation) -> Result<(), TokenError> {
+    let now = now();
+    let leeway = validation.leeway as usize;
+
+    if validation.validate_exp && claims.exp().saturating_add(leeway) < now {
+        return Err(TokenError::Validation(anyhow::anyhow!("token has expired")));
+    }
+    if validation.validate_nbf && claims.nbf() > now.saturating_add(leeway) {
+        return Err(TokenError::Validation(anyhow::anyhow!(
+            "token is not valid yet"
+        )));
+    }
+    if let Some(iss) = &validation.iss
+        && !iss.contains(claims.iss())
+    {
+        return Err(TokenError::Validation(anyhow::anyhow!(
+            "token issuer is invalid"
+        )));
+    }
+    if validation.validate_aud
+        && let Some(aud) = &validation.aud
+        && !aud.contains(claims.aud())
+    {
+        return Err(TokenError::Validation(anyhow::anyhow!(
+            "token audience is invalid"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_paseto, sign, validate, verify};
+    use crate::token::{
+        TokenType,
+        claims::PrimaryClaims,
+        keys::{AsymmetricKeys, KeyPair, Keys},
+    };
+    use crate::util::now;
+    use jsonwebtoken::{Algorithm, Validation};
+    use openssl::{pkey::PKey, rsa::Rsa};
+
+    fn keys() -> Keys {
+        let rsa = Rsa::generate(2048).unwrap();
+        let (private_key, public_key) = (
+            rsa.private_key_to_pem().unwrap(),
+            rsa.public_key_to_pem().unwrap(),
+        );
+        let pair = || KeyPair::from_rsa_pem(&private_key, &public_key).unwrap();
+        let ed25519 = PKey::generate_ed25519().unwrap();
+
+        Keys {
+            access: pair(),
+            refresh: pair(),
+            session: pair(),
+            reauth: pair(),
+            paseto: Some(
+                AsymmetricKeys::from_pem(
+                    &ed25519.private_key_to_pem_pkcs8().unwrap(),
+                    &ed25519.public_key_to_pem().unwrap(),
+                )
+                .unwrap(),
+            ),
+            session_encryption: None,
+        }
+    }
+
+    fn claims(exp: usize, nbf: usize) -> PrimaryClaims {
+        PrimaryClaims {
+            sub: String::from("sub"),
+            jti: String::from("jti"),
+            rjti: String::from("jti"),
+            exp,
+            iat: nbf,
+            nbf,
+            custom: None,
+            iss: String::from("iss"),
+            aud: String::from("aud"),
+            scope: None,
+            roles: None,
+            act: None,
+            cnf: None,
+        }
+    }
+
+    fn validation() -> Validation {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&["iss"]);
+        validation.set_audience(&["aud"]);
+        validation.validate_nbf = true;
+        validation
+    }
+
+    #[test]
+    fn validates_registered_claims() {
+        assert!(validate(&claims(now() + 60, now()), &validation()).is_ok());
+        assert!(validate(&claims(now() - 3600, now() - 7200), &validation()).is_err());
+        assert!(validate(&claims(now() + 7200, now() + 3600), &validation()).is_err());
+    }
+
+    #[test]
+    fn validates_extreme_timestamps_without_overflowing() {
+        assert!(validate(&claims(usize::MAX, 0), &validation()).is_ok());
+        assert!(validate(&claims(usize::MAX, usize::MAX), &validation()).is_err());
+    }
+
+    #[test]
+    fn round_trips_claims() {
+        let keys = keys();
+        let claims = claims(now() + 60, now());
+        let token = sign(&claims, TokenType::Access, &keys).unwrap();
+
+        assert!(is_paseto(&token));
+        let verified: PrimaryClaims =
+            verify(&token, TokenType::Access, &validation(), &keys).unwrap();
+        assert_eq!(verified.jti, claims.jti);
+        assert_eq!(verified.sub, claims.sub);
+    }
+
+    #[test]
+    fn rejects_a_token_of_another_type() {
+        let keys = keys();
+        let token = sign(&claims(now() + 60, now()), TokenType::Access, &keys).unwrap();
+
+        assert!(verify::<PrimaryClaims>(&token, TokenType::Refresh, &validation(), &keys).is_err());
+    }
+
+    #[test]
+    fn rejects_another_key() {
+        let token = sign(&claims(now() + 60, now()), TokenType::Access, &keys()).unwrap();
+
+        assert!(
+            verify::<PrimaryClaims>(&token, TokenType::Access, &validation(), &keys()).is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_a_token_from_another_audience() {
+        let keys = keys();
+        let mut claims = claims(now() + 60, now());
+        claims.aud = String::from("other");
+        let token = sign(&claims, TokenType::Access, &keys).unwrap();
+
+        assert!(verify::<PrimaryClaims>(&token, TokenType::Access, &validation(), &keys).is_err());
+    }
+}
@@ -0,0 +1,16 @@
+-- KEYS[1] refresh token key, KEYS[2] exchanged access token key
+-- KEYS[3] exchanged token index of the refresh token
+-- ARGV[1] exchanged access token jti, ARGV[2] access token value
+-- ARGV[3] access token ttl
+if redis.call('EXISTS', KEYS[1]) == 0 then
+  return 0
+end
+
+redis.call('SET', KEYS[2], ARGV[2], 'EX', ARGV[3])
+redis.call('SADD', KEYS[3], ARGV[1])
+-- the index only has to outlive the longest lived exchanged token
+if redis.call('TTL', KEYS[3]) < tonumber(ARGV[3]) then
+  redis.call('EXPIRE', KEYS[3], ARGV[3])
+end
+
+return 1
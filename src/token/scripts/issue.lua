@@ -0,0 +1,77 @@
+-- KEYS[1] refresh token key, KEYS[2] access token key, KEYS[3] user index key
+-- ARGV[1] access token jti, ARGV[2] user id
+-- ARGV[3] refresh token ttl, ARGV[4] access token ttl, ARGV[5] refresh token jti
+-- ARGV[6] session limit, 0 for none, ARGV[7] 'reject' or 'evict' at the limit
+-- ARGV[8] number of indexed refresh tokens read for the limit, for each one
+--   ARGV: jti, current access token jti or '', exchanged count, exchanged jtis
+--   KEYS: refresh token key, exchanged token index key, current access token
+--   key unless there is none, exchanged access token keys
+-- returns -1 when the indexed tokens changed since they were read
+local evicted = {}
+local limit = tonumber(ARGV[6])
+if limit > 0 then
+  local count = tonumber(ARGV[8])
+  if redis.call('SCARD', KEYS[3]) ~= count then
+    return -1
+  end
+
+  local live = {}
+  local k, a = 4, 9
+  for _ = 1, count do
+    local session = { rjti = ARGV[a], current = ARGV[a + 1], keys = { KEYS[k], KEYS[k + 1] } }
+    local exchanged = tonumber(ARGV[a + 2])
+    k = k + 2
+    if session.current ~= '' then
+      table.insert(session.keys, KEYS[k])
+      k = k + 1
+    end
+    for _ = 1, exchanged do
+      table.insert(session.keys, KEYS[k])
+      k = k + 1
+    end
+
+    if redis.call('SISMEMBER', KEYS[3], session.rjti) == 0
+      or (redis.call('GET', session.keys[1]) or '') ~= session.current
+      or redis.call('SCARD', session.keys[2]) ~= exchanged then
+      return -1
+    end
+    for i = 1, exchanged do
+      if redis.call('SISMEMBER', session.keys[2], ARGV[a + 2 + i]) == 0 then
+        return -1
+      end
+    end
+    a = a + 3 + exchanged
+
+    if session.current == '' then
+      redis.call('SREM', KEYS[3], session.rjti)
+    else
+      table.insert(live, session)
+    end
+  end
+
+  -- counting and issuing in one script keeps concurrent logins from both
+  -- slipping under the limit
+  if #live >= limit then
+    if ARGV[7] == 'reject' then
+      return false
+    end
+
+    -- jtis are ulids, so sorting them puts the oldest sessions first
+    table.sort(live, function(x, y) return x.rjti < y.rjti end)
+    for i = 1, #live - limit + 1 do
+      redis.call('DEL', unpack(live[i].keys))
+      redis.call('SREM', KEYS[3], live[i].rjti)
+      table.insert(evicted, live[i].rjti)
+    end
+  end
+end
+
+redis.call('SET', KEYS[1], ARGV[1], 'EX', ARGV[3])
+redis.call('SET', KEYS[2], ARGV[2], 'EX', ARGV[4])
+redis.call('SADD', KEYS[3], ARGV[5])
+-- the index has to outlive the longest lived refresh token of the user
+if redis.call('TTL', KEYS[3]) < tonumber(ARGV[3]) then
+  redis.call('EXPIRE', KEYS[3], ARGV[3])
+end
+
+return evicted
@@ -0,0 +1,12 @@
+-- KEYS[1] user index key, KEYS[2..] refresh token key of each indexed jti
+-- ARGV[1..] indexed refresh token jtis, in the same order as their keys
+local live = {}
+for i, rjti in ipairs(ARGV) do
+  if redis.call('EXISTS', KEYS[i + 1]) == 1 then
+    table.insert(live, rjti)
+  else
+    redis.call('SREM', KEYS[1], rjti)
+  end
+end
+
+return live
@@ -0,0 +1,22 @@
+-- KEYS[1] refresh token key, KEYS[2] exchanged token index of the refresh token
+-- KEYS[3] current access token key, KEYS[4..] exchanged access token keys
+-- ARGV[1] current access token jti the keys were read for
+-- ARGV[2..] exchanged access token jtis the keys were read for
+-- returns -1 when the tokens changed since they were read
+local current = redis.call('GET', KEYS[1])
+if not current then
+  return 0
+end
+
+if current ~= ARGV[1] or redis.call('SCARD', KEYS[2]) ~= #ARGV - 1 then
+  return -1
+end
+for i = 2, #ARGV do
+  if redis.call('SISMEMBER', KEYS[2], ARGV[i]) == 0 then
+    return -1
+  end
+end
+
+redis.call('DEL', unpack(KEYS))
+
+return 1
@@ -0,0 +1,27 @@
+-- KEYS[1] refresh token key, KEYS[2] new access token key, KEYS[3] user index key
+-- KEYS[4] current access token key
+-- ARGV[1] current access token jti the keys were read for
+-- ARGV[2] new access token jti, ARGV[3] access token value
+-- ARGV[4] access token ttl, ARGV[5] refresh token ttl, 0 keeps the current ttl
+-- returns -1 when the refresh token was rotated since it was read
+local current = redis.call('GET', KEYS[1])
+if not current then
+  return 0
+end
+if current ~= ARGV[1] then
+  return -1
+end
+
+redis.call('DEL', KEYS[4])
+if tonumber(ARGV[5]) > 0 then
+  redis.call('SET', KEYS[1], ARGV[2], 'EX', ARGV[5])
+  -- a sliding refresh token must stay listed for as long as it lives
+  if redis.call('TTL', KEYS[3]) < tonumber(ARGV[5]) then
+    redis.call('EXPIRE', KEYS[3], ARGV[5])
+  end
+else
+  redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+end
+redis.call('SET', KEYS[2], ARGV[3], 'EX', ARGV[4])
+
+return 1
@@ -2,11 +2,13 @@ use super::{
     claims::{Claims, ExtendedClaims, PrimaryClaims},
     params::TokenParams,
     response::{Factory, TokenResponse},
+    store::SessionCap,
     traits::Token,
     types::refresh::Refresh,
 };
 use crate::{
     config::state::AppState,
+    database,
     error::AppError,
     model::user::UserDetails,
     token::types::{access::Access, session::Session},
@@ -33,10 +35,25 @@ pub struct TokenFactory {
     pub session: Factory<ExtendedClaims>,
 }
 
-pub async fn factory(state: AppState, user: &UserDetails) -> Result<TokenFactory, AppError> {
+pub async fn factory(
+    state: AppState,
+    user: &UserDetails,
+    aud: &str,
+    jkt: Option<&str>,
+    remember_me: bool,
+    session_cap: Option<SessionCap>,
+) -> Result<TokenFactory, AppError> {
+    let grants = database::role::get_grants(&state.db, &user.id)
+        .await
+        .map_err(AppError::from_database_error)?;
+
     let refresh = create_token(
         Refresh::new(state.clone(), &user.id),
-        TokenParams::default(),
+        TokenParams::default()
+            .with_aud(aud.to_owned())
+            .with_jkt(jkt.map(str::to_owned))
+            .with_remember_me(remember_me)
+            .with_session_cap(session_cap),
     )
     .await?;
     let claims = refresh.claims().clone();
@@ -48,13 +65,18 @@ pub async fn factory(state: AppState, user: &UserDetails) -> Result<TokenFactory
         Access::new(state.clone(), &user.id),
         TokenParams::default()
             .with_ajti(access_token_jti.clone())
-            .with_rjti(claims.jti),
+            .with_rjti(claims.jti)
+            .with_aud(aud.to_owned())
+            .with_grants(grants.clone())
+            .with_jkt(jkt.map(str::to_owned)),
     )
     .await?;
 
     let session = create_token(
         Session::new(state.clone(), user.into()),
-        TokenParams::default(),
+        TokenParams::default()
+            .with_aud(aud.to_owned())
+            .with_grants(grants),
     )
     .await?;
 
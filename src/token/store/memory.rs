@@ -0,0 +1,462 @@
+use super::{SessionCap, TokenStore};
+use crate::config::env::SessionLimitAction;
+use crate::token::{TokenType, error::TokenError};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// how often writes sweep out tokens that expired without being read again
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
+struct Entry {
+    value: String,
+    expires_at: Instant,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: HashMap<(TokenType, String), Entry>,
+    users: HashMap<String, HashSet<String>>,
+    exchanged: HashMap<String, HashSet<String>>,
+    purged_at: Option<Instant>,
+}
+
+impl Inner {
+    fn get(&mut self, token_type: TokenType, jti: &str) -> Option<&Entry> {
+        let key = (token_type, jti.to_owned());
+        if self
+            .entries
+            .get(&key)
+            .is_some_and(|entry| entry.expires_at <= Instant::now())
+        {
+            self.entries.remove(&key);
+        }
+
+        self.entries.get(&key)
+    }
+
+    /// live refresh tokens of a user, dropping dead ones from the index
+    fn live(&mut self, user_id: &str) -> Vec<String> {
+        let rjtis: Vec<String> = self
+            .users
+            .get(user_id)
+            .map(|rjtis| rjtis.iter().cloned().collect())
+            .unwrap_or_default();
+
+        let live: Vec<String> = rjtis
+            .into_iter()
+            .filter(|rjti| self.get(TokenType::Refresh, rjti).is_some())
+            .collect();
+        match live.is_empty() {
+            true => self.users.remove(user_id),
+            false => self
+                .users
+                .insert(user_id.to_owned(), live.iter().cloned().collect()),
+        };
+
+        live
+    }
+
+    /// removes a refresh token with its access and exchanged tokens, returns
+    /// whether it was still live
+    fn revoke(&mut self, rjti: &str) -> bool {
+        let Some(refresh) = self.entries.remove(&(TokenType::Refresh, rjti.to_owned())) else {
+            return false;
+        };
+        self.entries.remove(&(TokenType::Access, refresh.value));
+        for ajti in self.exchanged.remove(rjti).unwrap_or_default() {
+            self.entries.remove(&(TokenType::Access, ajti));
+        }
+
+        refresh.expires_at > Instant::now()
+    }
+
+    /// drops expired tokens along with their index entries, reads only evict
+    /// the keys they touch so this keeps the store bounded
+    fn purge(&mut self, now: Instant) {
+        self.entries.retain(|_, entry| entry.expires_at > now);
+
+        let entries = &self.entries;
+        let live =
+            |token_type: TokenType, jti: &String| entries.contains_key(&(token_type, jti.clone()));
+        self.users.retain(|_, rjtis| {
+            rjtis.retain(|rjti| live(TokenType::Refresh, rjti));
+            !rjtis.is_empty()
+        });
+        self.exchanged.retain(|rjti, ajtis| {
+            ajtis.retain(|ajti| live(TokenType::Access, ajti));
+            live(TokenType::Refresh, rjti) && !ajtis.is_empty()
+        });
+
+        self.purged_at = Some(now);
+    }
+
+    fn put(&mut self, token_type: TokenType, jti: &str, value: &str, expires_at: Instant) {
+        let now = Instant::now();
+        if self
+            .purged_at
+            .is_none_or(|purged_at| now.duration_since(purged_at) >= PURGE_INTERVAL)
+        {
+            self.purge(now);
+        }
+
+        self.entries.insert(
+            (token_type, jti.to_owned()),
+            Entry {
+                value: value.to_owned(),
+                expires_at,
+            },
+        );
+    }
+}
+
+/// process local token store, only the tokens live here so redis is still
+/// required for everything else, and tokens are not shared between replicas
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MemoryStore {
+    fn lock(&self) -> Result<MutexGuard<'_, Inner>, TokenError> {
+        self.inner
+            .lock()
+            .map_err(|_| TokenError::Other(anyhow::anyhow!("token store lock is poisoned")))
+    }
+}
+
+fn expires_at(ttl: usize) -> Instant {
+    Instant::now() + Duration::from_secs(ttl as u64)
+}
+
+#[tonic::async_trait]
+impl TokenStore for MemoryStore {
+    async fn put(
+        &self,
+        token_type: TokenType,
+        jti: &str,
+        value: &str,
+        ttl: usize,
+    ) -> Result<(), TokenError> {
+        self.lock()?.put(token_type, jti, value, expires_at(ttl));
+        Ok(())
+    }
+
+    async fn get(&self, token_type: TokenType, jti: &str) -> Result<Option<String>, TokenError> {
+        Ok(self
+            .lock()?
+            .get(token_type, jti)
+            .map(|entry| entry.value.clone()))
+    }
+
+    async fn ttl(&self, token_type: TokenType, jti: &str) -> Result<Option<usize>, TokenError> {
+        Ok(self.lock()?.get(token_type, jti).map(|entry| {
+            entry
+                .expires_at
+                .saturating_duration_since(Instant::now())
+                .as_secs() as usize
+        }))
+    }
+
+    async fn issue(
+        &self,
+        user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        refresh_ttl: usize,
+        access_ttl: usize,
+        cap: Option<SessionCap>,
+    ) -> Result<Option<Vec<String>>, TokenError> {
+        let mut inner = self.lock()?;
+        let mut evicted = vec![];
+        if let Some(SessionCap { limit, action }) = cap.filter(|cap| cap.limit > 0) {
+            let mut live = inner.live(user_id);
+            if live.len() >= limit {
+                if action == SessionLimitAction::Reject {
+                    return Ok(None);
+                }
+
+                // jtis are ulids, so sorting them puts the oldest sessions first
+                live.sort();
+                let excess = live.len() - limit + 1;
+                for rjti in live.into_iter().take(excess) {
+                    inner.revoke(&rjti);
+                    inner
+                        .users
+                        .entry(user_id.to_owned())
+                        .or_default()
+                        .remove(&rjti);
+                    evicted.push(rjti);
+                }
+            }
+        }
+
+        inner.put(TokenType::Refresh, rjti, ajti, expires_at(refresh_ttl));
+        inner.put(TokenType::Access, ajti, user_id, expires_at(access_ttl));
+        inner
+            .users
+            .entry(user_id.to_owned())
+            .or_default()
+            .insert(rjti.to_owned());
+
+        Ok(Some(evicted))
+    }
+
+    async fn rotate(
+        &self,
+        _user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        access_ttl: usize,
+        refresh_ttl: Option<usize>,
+    ) -> Result<bool, TokenError> {
+        let mut inner = self.lock()?;
+        let Some(refresh) = inner.get(TokenType::Refresh, rjti) else {
+            return Ok(false);
+        };
+        let (current, refresh_expires_at) = (
+            refresh.value.clone(),
+            refresh_ttl.map_or(refresh.expires_at, expires_at),
+        );
+
+        inner.entries.remove(&(TokenType::Access, current));
+        inner.put(TokenType::Refresh, rjti, ajti, refresh_expires_at);
+        inner.put(TokenType::Access, ajti, value, expires_at(access_ttl));
+
+        Ok(true)
+    }
+
+    async fn put_exchanged(
+        &self,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        ttl: usize,
+    ) -> Result<bool, TokenError> {
+        let mut inner = self.lock()?;
+        if inner.get(TokenType::Refresh, rjti).is_none() {
+            return Ok(false);
+        }
+
+        inner.put(TokenType::Access, ajti, value, expires_at(ttl));
+        inner
+            .exchanged
+            .entry(rjti.to_owned())
+            .or_default()
+            .insert(ajti.to_owned());
+
+        Ok(true)
+    }
+
+    async fn revoke(&self, rjti: &str) -> Result<bool, TokenError> {
+        Ok(self.lock()?.revoke(rjti))
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<String>, TokenError> {
+        Ok(self.lock()?.live(user_id))
+    }
+
+    async fn scan_refresh(
+        &self,
+        _cursor: u64,
+        _count: usize,
+    ) -> Result<(u64, Vec<String>), TokenError> {
+        let now = Instant::now();
+
+        Ok((
+            0,
+            self.lock()?
+                .entries
+                .iter()
+                .filter(|((token_type, _), entry)| {
+                    *token_type == TokenType::Refresh && entry.expires_at > now
+                })
+                .map(|((_, jti), _)| jti.clone())
+                .collect(),
+        ))
+    }
+
+    async fn refresh_exists(&self, rjtis: &[String]) -> Result<Vec<bool>, TokenError> {
+        let mut inner = self.lock()?;
+
+        Ok(rjtis
+            .iter()
+            .map(|rjti| inner.get(TokenType::Refresh, rjti).is_some())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Inner, MemoryStore};
+    use crate::{
+        config::env::SessionLimitAction,
+        token::{
+            TokenType,
+            store::{SessionCap, TokenStore},
+        },
+    };
+
+    fn cap(action: SessionLimitAction) -> Option<SessionCap> {
+        Some(SessionCap { limit: 2, action })
+    }
+
+    #[tokio::test]
+    async fn revoke_cascades_to_exchanged_tokens() {
+        let store = MemoryStore::default();
+        store
+            .issue("user", "refresh", "access", 3600, 900, None)
+            .await
+            .unwrap();
+        assert!(
+            store
+                .put_exchanged("refresh", "exchanged", "user", 900)
+                .await
+                .unwrap()
+        );
+
+        assert!(store.revoke("refresh").await.unwrap());
+        assert_eq!(store.get(TokenType::Access, "access").await.unwrap(), None);
+        assert_eq!(
+            store.get(TokenType::Access, "exchanged").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn exchange_requires_a_live_refresh_token() {
+        let store = MemoryStore::default();
+
+        assert!(
+            !store
+                .put_exchanged("missing", "exchanged", "user", 900)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn list_by_user_only_returns_live_refresh_tokens() {
+        let store = MemoryStore::default();
+        store
+            .issue("user", "first", "a1", 3600, 900, None)
+            .await
+            .unwrap();
+        store
+            .issue("user", "second", "a2", 3600, 900, None)
+            .await
+            .unwrap();
+        store
+            .issue("other", "third", "a3", 3600, 900, None)
+            .await
+            .unwrap();
+        store.revoke("first").await.unwrap();
+
+        assert_eq!(store.list_by_user("user").await.unwrap(), ["second"]);
+        assert!(store.list_by_user("nobody").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn issue_evicts_the_oldest_refresh_tokens_at_the_cap() {
+        let store = MemoryStore::default();
+        let evict = cap(SessionLimitAction::Evict);
+        store
+            .issue("user", "01A", "a1", 3600, 900, evict)
+            .await
+            .unwrap();
+        store
+            .issue("user", "01B", "a2", 3600, 900, evict)
+            .await
+            .unwrap();
+        store.put_exchanged("01A", "x1", "user", 900).await.unwrap();
+
+        let evicted = store
+            .issue("user", "01C", "a3", 3600, 900, evict)
+            .await
+            .unwrap();
+        assert_eq!(evicted, Some(vec![String::from("01A")]));
+
+        let mut live = store.list_by_user("user").await.unwrap();
+        live.sort();
+        assert_eq!(live, ["01B", "01C"]);
+        assert_eq!(store.get(TokenType::Access, "a1").await.unwrap(), None);
+        assert_eq!(store.get(TokenType::Access, "x1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn issue_rejects_past_the_cap_without_storing_anything() {
+        let store = MemoryStore::default();
+        let reject = cap(SessionLimitAction::Reject);
+        store
+            .issue("user", "01A", "a1", 3600, 900, reject)
+            .await
+            .unwrap();
+        store
+            .issue("user", "01B", "a2", 3600, 900, reject)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store
+                .issue("user", "01C", "a3", 3600, 900, reject)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(store.get(TokenType::Refresh, "01C").await.unwrap(), None);
+        assert_eq!(store.list_by_user("user").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn revoked_tokens_do_not_count_against_the_cap() {
+        let store = MemoryStore::default();
+        let reject = cap(SessionLimitAction::Reject);
+        store
+            .issue("user", "01A", "a1", 3600, 900, reject)
+            .await
+            .unwrap();
+        store
+            .issue("user", "01B", "a2", 3600, 900, reject)
+            .await
+            .unwrap();
+        store.revoke("01A").await.unwrap();
+
+        assert_eq!(
+            store
+                .issue("user", "01C", "a3", 3600, 900, reject)
+                .await
+                .unwrap(),
+            Some(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn purge_drops_expired_tokens_and_their_index_entries() {
+        let store = MemoryStore::default();
+        store.issue("user", "01A", "a1", 0, 0, None).await.unwrap();
+        store
+            .issue("user", "01B", "a2", 3600, 0, None)
+            .await
+            .unwrap();
+        store.put_exchanged("01B", "x2", "user", 0).await.unwrap();
+        store.issue("other", "01C", "a3", 0, 0, None).await.unwrap();
+
+        let mut inner = store.lock().unwrap();
+        inner.purge(std::time::Instant::now());
+
+        let Inner {
+            entries,
+            users,
+            exchanged,
+            ..
+        } = &*inner;
+        let keys: Vec<_> = entries.keys().cloned().collect();
+        assert_eq!(keys, [(TokenType::Refresh, String::from("01B"))]);
+        assert_eq!(users.len(), 1);
+        assert!(users["user"].contains("01B"));
+        assert!(exchanged.is_empty());
+    }
+}
@@ -0,0 +1,88 @@
+use super::{TokenType, error::TokenError};
+use crate::config::env::SessionLimitAction;
+
+pub mod memory;
+pub mod redis;
+
+pub use self::{memory::MemoryStore, redis::RedisStore};
+
+/// how many refresh tokens a user may hold at once and what happens to a new
+/// one past that
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionCap {
+    pub limit: usize,
+    pub action: SessionLimitAction,
+}
+
+#[tonic::async_trait]
+pub trait TokenStore: Send + Sync {
+    async fn put(
+        &self,
+        token_type: TokenType,
+        jti: &str,
+        value: &str,
+        ttl: usize,
+    ) -> Result<(), TokenError>;
+
+    async fn get(&self, token_type: TokenType, jti: &str) -> Result<Option<String>, TokenError>;
+
+    /// remaining lifetime in seconds, `None` when the token does not exist
+    async fn ttl(&self, token_type: TokenType, jti: &str) -> Result<Option<usize>, TokenError>;
+
+    /// stores a new refresh token together with its first access token, the
+    /// cap is checked against the live refresh tokens of the user in the same
+    /// step, returns the refresh tokens revoked to make room or `None` when
+    /// the cap rejected the new one
+    async fn issue(
+        &self,
+        user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        refresh_ttl: usize,
+        access_ttl: usize,
+        cap: Option<SessionCap>,
+    ) -> Result<Option<Vec<String>>, TokenError>;
+
+    /// replaces the access token bound to the refresh token and stores `value`
+    /// against the new access token, `refresh_ttl` slides the refresh token
+    /// expiry when given, returns false when the refresh token no longer exists
+    async fn rotate(
+        &self,
+        user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        access_ttl: usize,
+        refresh_ttl: Option<usize>,
+    ) -> Result<bool, TokenError>;
+
+    /// stores an access token issued through token exchange and indexes it
+    /// under the refresh token of its subject, returns false when the refresh
+    /// token no longer exists
+    async fn put_exchanged(
+        &self,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        ttl: usize,
+    ) -> Result<bool, TokenError>;
+
+    /// removes the refresh token, its current access token and every token
+    /// exchanged from it, returns false when the refresh token no longer exists
+    async fn revoke(&self, rjti: &str) -> Result<bool, TokenError>;
+
+    /// refresh token jtis that are still live for the given user, the index
+    /// is written together with the tokens so it is current right after login
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<String>, TokenError>;
+
+    /// a page of refresh token jtis across all users, scanning starts at cursor
+    /// 0 and is done once the returned cursor is 0 again
+    async fn scan_refresh(
+        &self,
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<String>), TokenError>;
+
+    /// whether each of the given refresh tokens still exists
+    async fn refresh_exists(&self, rjtis: &[String]) -> Result<Vec<bool>, TokenError>;
+}
@@ -0,0 +1,361 @@
+use super::{SessionCap, TokenStore};
+use crate::{
+    config::{ENV, env::SessionLimitAction},
+    token::{TokenType, error::TokenError},
+};
+use once_cell::sync::Lazy;
+use redis::{Client, Script, ScriptInvocation, Value, aio::MultiplexedConnection};
+
+static ISSUE: Lazy<Script> = Lazy::new(|| Script::new(include_str!("../scripts/issue.lua")));
+static ROTATE: Lazy<Script> = Lazy::new(|| Script::new(include_str!("../scripts/rotate.lua")));
+static EXCHANGE: Lazy<Script> = Lazy::new(|| Script::new(include_str!("../scripts/exchange.lua")));
+static REVOKE: Lazy<Script> = Lazy::new(|| Script::new(include_str!("../scripts/revoke.lua")));
+static LIST: Lazy<Script> = Lazy::new(|| Script::new(include_str!("../scripts/list.lua")));
+
+#[derive(Clone)]
+pub struct RedisStore {
+    rd: Client,
+}
+
+impl RedisStore {
+    pub fn new(rd: Client) -> Self {
+        Self { rd }
+    }
+
+    async fn conn(&self) -> Result<MultiplexedConnection, TokenError> {
+        self.rd
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|err| TokenError::Other(err.into()))
+    }
+}
+
+fn user_key(user_id: &str) -> String {
+    format!("{}:user_tokens:{}", &*ENV.redis_schema, user_id)
+}
+
+fn exchanged_key(rjti: &str) -> String {
+    format!("{}:exchanged_tokens:{}", &*ENV.redis_schema, rjti)
+}
+
+/// scripts that touch tokens read up front report this when the tokens
+/// changed in between, they are prepared again a few times before giving up
+const CONFLICT: i64 = -1;
+const CONFLICT_RETRIES: usize = 5;
+
+fn other(err: redis::RedisError) -> TokenError {
+    TokenError::Other(err.into())
+}
+
+fn conflict() -> TokenError {
+    TokenError::Other(anyhow::anyhow!(
+        "tokens kept changing while they were being updated"
+    ))
+}
+
+/// a refresh token with the access tokens hanging off it, read before a
+/// script runs so every key the script touches can be declared up front
+struct Family {
+    rjti: String,
+    current: Option<String>,
+    exchanged: Vec<String>,
+}
+
+impl Family {
+    /// declares the current access token key, when there is one, followed by
+    /// the exchanged access token keys
+    fn declare_access(&self, invocation: &mut ScriptInvocation) {
+        if let Some(current) = &self.current {
+            invocation.key(TokenType::Access.get_key(current));
+        }
+        for ajti in &self.exchanged {
+            invocation.key(TokenType::Access.get_key(ajti));
+        }
+    }
+}
+
+async fn families(
+    conn: &mut MultiplexedConnection,
+    rjtis: &[String],
+) -> Result<Vec<Family>, TokenError> {
+    if rjtis.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut pipe = redis::pipe();
+    for rjti in rjtis {
+        pipe.get(TokenType::Refresh.get_key(rjti))
+            .smembers(exchanged_key(rjti));
+    }
+    let values: Vec<Value> = pipe.query_async(conn).await.map_err(other)?;
+
+    rjtis
+        .iter()
+        .zip(values.chunks(2))
+        .map(|(rjti, values)| {
+            Ok(Family {
+                rjti: rjti.clone(),
+                current: redis::from_redis_value(&values[0]).map_err(other)?,
+                exchanged: redis::from_redis_value(&values[1]).map_err(other)?,
+            })
+        })
+        .collect()
+}
+
+#[tonic::async_trait]
+impl TokenStore for RedisStore {
+    async fn put(
+        &self,
+        token_type: TokenType,
+        jti: &str,
+        value: &str,
+        ttl: usize,
+    ) -> Result<(), TokenError> {
+        let mut conn = self.conn().await?;
+
+        redis::cmd("SET")
+            .arg(token_type.get_key(jti))
+            .arg(value)
+            .arg("EX")
+            .arg(ttl)
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))
+    }
+
+    async fn get(&self, token_type: TokenType, jti: &str) -> Result<Option<String>, TokenError> {
+        let mut conn = self.conn().await?;
+
+        redis::cmd("GET")
+            .arg(token_type.get_key(jti))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))
+    }
+
+    async fn ttl(&self, token_type: TokenType, jti: &str) -> Result<Option<usize>, TokenError> {
+        let mut conn = self.conn().await?;
+
+        let ttl: i64 = redis::cmd("TTL")
+            .arg(token_type.get_key(jti))
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))?;
+
+        Ok(ttl.try_into().ok())
+    }
+
+    async fn issue(
+        &self,
+        user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        refresh_ttl: usize,
+        access_ttl: usize,
+        cap: Option<SessionCap>,
+    ) -> Result<Option<Vec<String>>, TokenError> {
+        let mut conn = self.conn().await?;
+
+        let (limit, action) = match cap {
+            Some(SessionCap { limit, action }) => (limit, action),
+            None => (0, SessionLimitAction::default()),
+        };
+        for _ in 0..CONFLICT_RETRIES {
+            let families = match limit {
+                0 => vec![],
+                _ => {
+                    let rjtis: Vec<String> = redis::cmd("SMEMBERS")
+                        .arg(user_key(user_id))
+                        .query_async(&mut conn)
+                        .await
+                        .map_err(other)?;
+                    families(&mut conn, &rjtis).await?
+                }
+            };
+
+            let mut invocation = ISSUE.prepare_invoke();
+            invocation
+                .key(TokenType::Refresh.get_key(rjti))
+                .key(TokenType::Access.get_key(ajti))
+                .key(user_key(user_id))
+                .arg(ajti)
+                .arg(user_id)
+                .arg(refresh_ttl)
+                .arg(access_ttl)
+                .arg(rjti)
+                .arg(limit)
+                .arg(match action {
+                    SessionLimitAction::Reject => "reject",
+                    SessionLimitAction::Evict => "evict",
+                })
+                .arg(families.len());
+            for family in &families {
+                invocation
+                    .key(TokenType::Refresh.get_key(&family.rjti))
+                    .key(exchanged_key(&family.rjti))
+                    .arg(&family.rjti)
+                    .arg(family.current.as_deref().unwrap_or_default())
+                    .arg(family.exchanged.len())
+                    .arg(&family.exchanged);
+                family.declare_access(&mut invocation);
+            }
+
+            match invocation.invoke_async(&mut conn).await.map_err(other)? {
+                Value::Nil => return Ok(None),
+                Value::Int(CONFLICT) => continue,
+                evicted => return redis::from_redis_value(&evicted).map(Some).map_err(other),
+            }
+        }
+
+        Err(conflict())
+    }
+
+    async fn rotate(
+        &self,
+        user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        access_ttl: usize,
+        refresh_ttl: Option<usize>,
+    ) -> Result<bool, TokenError> {
+        let mut conn = self.conn().await?;
+
+        for _ in 0..CONFLICT_RETRIES {
+            let current: Option<String> = redis::cmd("GET")
+                .arg(TokenType::Refresh.get_key(rjti))
+                .query_async(&mut conn)
+                .await
+                .map_err(other)?;
+            let Some(current) = current else {
+                return Ok(false);
+            };
+
+            let rotated: i64 = ROTATE
+                .key(TokenType::Refresh.get_key(rjti))
+                .key(TokenType::Access.get_key(ajti))
+                .key(user_key(user_id))
+                .key(TokenType::Access.get_key(&current))
+                .arg(&current)
+                .arg(ajti)
+                .arg(value)
+                .arg(access_ttl)
+                .arg(refresh_ttl.unwrap_or_default())
+                .invoke_async(&mut conn)
+                .await
+                .map_err(other)?;
+            if rotated != CONFLICT {
+                return Ok(rotated == 1);
+            }
+        }
+
+        Err(conflict())
+    }
+
+    async fn put_exchanged(
+        &self,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        ttl: usize,
+    ) -> Result<bool, TokenError> {
+        let mut conn = self.conn().await?;
+
+        EXCHANGE
+            .key(TokenType::Refresh.get_key(rjti))
+            .key(TokenType::Access.get_key(ajti))
+            .key(exchanged_key(rjti))
+            .arg(ajti)
+            .arg(value)
+            .arg(ttl)
+            .invoke_async(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))
+    }
+
+    async fn revoke(&self, rjti: &str) -> Result<bool, TokenError> {
+        let mut conn = self.conn().await?;
+
+        for _ in 0..CONFLICT_RETRIES {
+            let Some(family) = families(&mut conn, &[rjti.to_owned()]).await?.pop() else {
+                return Ok(false);
+            };
+            let Some(current) = &family.current else {
+                return Ok(false);
+            };
+
+            let mut invocation = REVOKE.prepare_invoke();
+            invocation
+                .key(TokenType::Refresh.get_key(rjti))
+                .key(exchanged_key(rjti))
+                .arg(current);
+            family.declare_access(&mut invocation);
+            invocation.arg(&family.exchanged);
+
+            let revoked: i64 = invocation.invoke_async(&mut conn).await.map_err(other)?;
+            if revoked != CONFLICT {
+                return Ok(revoked == 1);
+            }
+        }
+
+        Err(conflict())
+    }
+
+    async fn list_by_user(&self, user_id: &str) -> Result<Vec<String>, TokenError> {
+        let mut conn = self.conn().await?;
+
+        let rjtis: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(user_key(user_id))
+            .query_async(&mut conn)
+            .await
+            .map_err(other)?;
+        if rjtis.is_empty() {
+            return Ok(rjtis);
+        }
+
+        let mut invocation = LIST.prepare_invoke();
+        invocation.key(user_key(user_id));
+        for rjti in &rjtis {
+            invocation.key(TokenType::Refresh.get_key(rjti)).arg(rjti);
+        }
+        invocation.invoke_async(&mut conn).await.map_err(other)
+    }
+
+    async fn scan_refresh(
+        &self,
+        cursor: u64,
+        count: usize,
+    ) -> Result<(u64, Vec<String>), TokenError> {
+        let mut conn = self.conn().await?;
+
+        let prefix = TokenType::Refresh.get_key("");
+        let (cursor, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(format!("{}*", prefix))
+            .arg("COUNT")
+            .arg(count)
+            .query_async(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))?;
+
+        Ok((
+            cursor,
+            keys.into_iter()
+                .filter_map(|key| key.strip_prefix(&prefix).map(str::to_owned))
+                .collect(),
+        ))
+    }
+
+    async fn refresh_exists(&self, rjtis: &[String]) -> Result<Vec<bool>, TokenError> {
+        let mut conn = self.conn().await?;
+
+        let mut pipe = redis::pipe();
+        for rjti in rjtis {
+            pipe.exists(TokenType::Refresh.get_key(rjti));
+        }
+        pipe.query_async(&mut conn)
+            .await
+            .map_err(|err| TokenError::Other(err.into()))
+    }
+}
@@ -1,8 +1,16 @@
 use super::{
-    TokenType, claims::Claims, error::TokenError, params::TokenParams, response::TokenResponse,
+    Audience, TokenType,
+    claims::Claims,
+    dpop::{Confirmation, Proof},
+    epoch,
+    error::TokenError,
+    keys::{KeyPair, Keys},
+    params::TokenParams,
+    paseto,
+    response::TokenResponse,
 };
-use crate::config::state::AppState;
-use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use crate::config::{ENV, env::TokenFormat, state::AppState};
+use jsonwebtoken::{Algorithm, Header, Validation};
 use serde::{Deserialize, Serialize};
 
 pub trait Token<T>
@@ -14,33 +22,53 @@ where
 {
     fn state(&self) -> AppState;
 
-    fn public_key(&self) -> &[u8];
-    fn private_key(&self) -> &[u8];
+    fn keys(&self) -> &Keys;
     fn exp(&self) -> usize;
+    fn token_type(&self) -> TokenType;
+    fn format(&self) -> TokenFormat;
 
-    fn encode_rsa(&self, key: &[u8]) -> EncodingKey {
-        EncodingKey::from_rsa_pem(key).unwrap()
+    fn key_pair(&self) -> &KeyPair {
+        self.keys().get(self.token_type())
     }
-    fn decode_rsa(&self, key: &[u8]) -> DecodingKey {
-        DecodingKey::from_rsa_pem(key).unwrap()
+
+    fn validation(&self, audience: Audience) -> Validation {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&*ENV.token_issuer]);
+        match audience {
+            Audience::Exact(audience) => validation.set_audience(&[audience]),
+            Audience::Registered => validation.set_audience(&ENV.token_audiences),
+        }
+        validation.set_required_spec_claims(&["exp", "nbf", "sub", "iss", "aud"]);
+        validation
     }
 
     fn generate(&self, claims: &T) -> Result<String, TokenError> {
-        jsonwebtoken::encode(
-            &Header::new(Algorithm::RS256),
-            claims,
-            &self.encode_rsa(self.private_key()),
-        )
-        .map_err(|err| TokenError::Creation(err.into()))
+        match self.format() {
+            TokenFormat::Jwt => jsonwebtoken::encode(
+                &Header::new(Algorithm::RS256),
+                claims,
+                &self.key_pair().encoding,
+            )
+            .map_err(|err| TokenError::Creation(err.into())),
+            TokenFormat::Paseto => paseto::sign(claims, self.token_type(), self.keys()),
+        }
+    }
+    fn decode(&self, token: &str, audience: Audience) -> Result<T, TokenError> {
+        self.decode_with(token, &self.validation(audience))
+    }
+    fn decode_with(&self, token: &str, validation: &Validation) -> Result<T, TokenError> {
+        self.decode_signed(token, validation)
     }
-    fn decode(&self, token: &str) -> Result<T, TokenError> {
-        let claims = jsonwebtoken::decode::<T>(
-            token,
-            &self.decode_rsa(self.public_key()),
-            &Validation::new(Algorithm::RS256),
-        )
-        .map_err(|err| TokenError::Validation(err.into()))?
-        .claims;
+    /// dispatches on the token prefix so tokens issued before a format change
+    /// keep verifying until they expire
+    fn decode_signed(&self, token: &str, validation: &Validation) -> Result<T, TokenError> {
+        if paseto::is_paseto(token) {
+            return paseto::verify(token, self.token_type(), validation, self.keys());
+        }
+
+        let claims = jsonwebtoken::decode::<T>(token, &self.key_pair().decoding, validation)
+            .map_err(|err| TokenError::Validation(err.into()))?
+            .claims;
 
         Ok(claims)
     }
@@ -54,24 +82,80 @@ where
         &self,
         token: &str,
         token_type: TokenType,
+        audience: Audience,
+        proof: Option<&Proof>,
+    ) -> impl Future<Output = Result<T, TokenError>> + Send
+    where
+        T: Send,
+    {
+        async move {
+            let claims = self.decode(token, audience)?;
+            let claims = self.lookup(claims, token_type).await?;
+            self.current(claims.sub(), claims.jti(), claims.iat())
+                .await?;
+            self.confirm(claims.cnf(), token, token_type, proof).await?;
+            Ok(claims)
+        }
+    }
+
+    /// rejects tokens issued before the user's token epoch
+    fn current(
+        &self,
+        sub: &str,
+        jti: &str,
+        iat: usize,
+    ) -> impl Future<Output = Result<(), TokenError>> + Send {
+        async move {
+            let epoch = epoch::get(&self.state(), sub).await?;
+            if epoch::revokes(epoch, epoch::issued_at(jti, iat)) {
+                return Err(TokenError::Validation(anyhow::anyhow!(
+                    "token has been revoked"
+                )));
+            }
+
+            Ok(())
+        }
+    }
+
+    /// sender-constrained tokens require a dpop proof made with the bound key,
+    /// access token proofs must also carry the hash of the token
+    fn confirm(
+        &self,
+        cnf: Option<&Confirmation>,
+        token: &str,
+        token_type: TokenType,
+        proof: Option<&Proof>,
+    ) -> impl Future<Output = Result<(), TokenError>> + Send {
+        async move {
+            let Some(cnf) = cnf else {
+                return Ok(());
+            };
+            let proof = proof.ok_or_else(|| {
+                TokenError::Validation(anyhow::anyhow!("token requires a dpop proof"))
+            })?;
+
+            let access_token = (token_type == TokenType::Access).then_some(token);
+            let jkt = proof.verify(&self.state(), access_token).await?;
+            if jkt != cnf.jkt {
+                return Err(TokenError::Validation(anyhow::anyhow!(
+                    "dpop proof key does not match the token"
+                )));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn lookup(
+        &self,
+        claims: T,
+        token_type: TokenType,
     ) -> impl Future<Output = Result<T, TokenError>> + Send
     where
         T: Send,
     {
         async move {
-            let claims = self.decode(token)?;
-
-            let mut conn = self
-                .state()
-                .get_redis_conn()
-                .await
-                .map_err(TokenError::Other)?;
-
-            let value: Option<String> = redis::cmd("GET")
-                .arg(token_type.get_key(claims.jti()))
-                .query_async(&mut conn)
-                .await
-                .map_err(|err| TokenError::Other(err.into()))?;
+            let value = self.state().store.get(token_type, claims.jti()).await?;
             let value =
                 value.ok_or_else(|| TokenError::Validation(anyhow::anyhow!("token not found")))?;
 
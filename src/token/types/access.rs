@@ -1,15 +1,43 @@
 use crate::{
-    config::{ENV, state::AppState},
+    config::{
+        ENV,
+        env::{AccessTokenMode, TokenFormat},
+        state::AppState,
+    },
+    database,
+    model::role::Grants,
     token::{
-        TokenType,
-        claims::{Claims, PrimaryClaims},
+        Audience, TokenType,
+        claims::{Actor, Claims, PrimaryClaims},
+        dpop::Proof,
         error::TokenError,
+        keys::Keys,
         params::TokenParams,
         response::{Factory, TokenResponse},
         traits::Token,
     },
+    util::{generate_secret, hash_secret, now},
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
 };
 
+const OPAQUE_PREFIX: &str = "at_";
+const OPAQUE_CACHE_CAPACITY: usize = 10_000;
+
+static OPAQUE_CACHE: Lazy<Mutex<HashMap<String, (PrimaryClaims, Instant)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Serialize, Deserialize)]
+struct OpaqueAccess {
+    secret: String,
+    claims: PrimaryClaims,
+}
+
 pub struct Access {
     pub state: AppState,
     pub user_id: Option<String>,
@@ -44,6 +72,93 @@ impl Access {
             params.rjti,
             None,
         )
+        .with_aud(params.aud)
+        .with_grants(params.grants.as_ref())
+        .with_cnf(params.jkt)
+    }
+
+    /// returns the token handed to the client and the value stored against
+    /// the access token jti
+    fn encode(&self, claims: &PrimaryClaims) -> Result<(String, String), TokenError> {
+        match ENV.access_token_mode {
+            AccessTokenMode::Jwt => Ok((self.generate(claims)?, claims.sub().to_owned())),
+            AccessTokenMode::Opaque => {
+                let secret = generate_secret();
+                let value = serde_json::to_string(&OpaqueAccess {
+                    secret: hash_secret(&secret),
+                    claims: claims.clone(),
+                })
+                .map_err(|err| TokenError::Creation(err.into()))?;
+
+                Ok((
+                    format!("{}{}_{}", OPAQUE_PREFIX, claims.jti(), secret),
+                    value,
+                ))
+            }
+        }
+    }
+
+    /// drops cached claims of the access tokens of a refresh token, only the
+    /// cache of this instance, the short cache ttl bounds the others
+    pub fn evict(rjti: &str) {
+        if let Ok(mut cache) = OPAQUE_CACHE.lock() {
+            cache.retain(|_, (claims, _)| claims.rjti() != rjti);
+        }
+    }
+
+    async fn resolve(&self, token: &str) -> Result<PrimaryClaims, TokenError> {
+        let (ajti, secret) = token
+            .strip_prefix(OPAQUE_PREFIX)
+            .and_then(|token| token.split_once('_'))
+            .ok_or_else(|| {
+                TokenError::InvalidFormat(anyhow::anyhow!("access token is not an opaque token"))
+            })?;
+
+        if ENV.opaque_token_cache_ttl > 0 {
+            let cache = OPAQUE_CACHE
+                .lock()
+                .map_err(|_| TokenError::Other(anyhow::anyhow!("token cache lock is poisoned")))?;
+            if let Some((claims, cached_at)) = cache.get(token)
+                && cached_at.elapsed() < Duration::from_secs(ENV.opaque_token_cache_ttl as u64)
+                && claims.exp() > now()
+            {
+                return Ok(claims.clone());
+            }
+        }
+
+        let value = self
+            .state()
+            .store
+            .get(TokenType::Access, ajti)
+            .await?
+            .ok_or_else(|| TokenError::Validation(anyhow::anyhow!("token not found")))?;
+        let stored: OpaqueAccess =
+            serde_json::from_str(&value).map_err(|err| TokenError::Parsing(err.into()))?;
+        if stored.secret != hash_secret(secret) {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "access token is invalid"
+            )));
+        }
+        if stored.claims.exp() <= now() {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "access token has expired"
+            )));
+        }
+
+        if ENV.opaque_token_cache_ttl > 0 {
+            let mut cache = OPAQUE_CACHE
+                .lock()
+                .map_err(|_| TokenError::Other(anyhow::anyhow!("token cache lock is poisoned")))?;
+            if cache.len() >= OPAQUE_CACHE_CAPACITY {
+                let ttl = Duration::from_secs(ENV.opaque_token_cache_ttl as u64);
+                cache.retain(|_, (_, cached_at)| cached_at.elapsed() < ttl);
+            }
+            if cache.len() < OPAQUE_CACHE_CAPACITY {
+                cache.insert(token.to_owned(), (stored.claims.clone(), Instant::now()));
+            }
+        }
+
+        Ok(stored.claims)
     }
 }
 
@@ -52,15 +167,18 @@ impl Token<PrimaryClaims> for Access {
         self.state.clone()
     }
 
-    fn public_key(&self) -> &[u8] {
-        &ENV.access_token_public_key
-    }
-    fn private_key(&self) -> &[u8] {
-        &ENV.access_token_private_key
+    fn keys(&self) -> &Keys {
+        &self.state.keys
     }
     fn exp(&self) -> usize {
         ENV.access_token_expiration
     }
+    fn token_type(&self) -> TokenType {
+        TokenType::Access
+    }
+    fn format(&self) -> TokenFormat {
+        ENV.access_token_format
+    }
 
     async fn create(
         &self,
@@ -70,97 +188,406 @@ impl Token<PrimaryClaims> for Access {
         let rjti = params
             .rjti
             .expect("refresh token jti is required to create an access token");
+        let claims = self.claims(TokenParams {
+            ajti: Some(ajti.clone()),
+            rjti: Some(rjti.clone()),
+            aud: params.aud,
+            grants: params.grants,
+            jkt: params.jkt,
+            remember_me: params.remember_me,
+            session_cap: params.session_cap,
+        });
+        let (token, value) = self.encode(&claims)?;
+
+        match (params.ajti.is_some(), ENV.access_token_mode) {
+            // the refresh token already stored the access token jti
+            (true, AccessTokenMode::Jwt) => {}
+            (true, AccessTokenMode::Opaque) => {
+                self.state()
+                    .store
+                    .put(TokenType::Access, &ajti, &value, self.exp())
+                    .await?
+            }
+            (false, _) => {
+                self.rotate(claims.sub(), &rjti, &ajti, &value, None)
+                    .await?
+            }
+        }
+
+        Ok(TokenResponse::Access(Factory::new(claims, token)))
+    }
+
+    async fn verify(
+        &self,
+        token: &str,
+        token_type: TokenType,
+        audience: Audience<'_>,
+        proof: Option<&Proof>,
+    ) -> Result<PrimaryClaims, TokenError>
+    where
+        PrimaryClaims: Send,
+    {
+        let claims = self.inspect(token, audience).await?;
+        self.confirm(claims.cnf(), token, token_type, proof).await?;
+
+        Ok(claims)
+    }
+}
+
+impl Access {
+    /// resolves the claims of an access token without checking its dpop binding,
+    /// resource servers verify the proof themselves against the returned cnf
+    pub async fn inspect(
+        &self,
+        token: &str,
+        audience: Audience<'_>,
+    ) -> Result<PrimaryClaims, TokenError> {
+        let claims = match ENV.access_token_mode {
+            AccessTokenMode::Jwt => {
+                let claims = self.decode(token, audience)?;
+                self.lookup(claims, TokenType::Access).await?
+            }
+            AccessTokenMode::Opaque => {
+                let claims = self.resolve(token).await?;
+                if !audience.accepts(claims.aud()) {
+                    return Err(TokenError::Validation(anyhow::anyhow!(
+                        "access token was not issued for this audience"
+                    )));
+                }
+
+                claims
+            }
+        };
+        self.current(claims.sub(), claims.jti(), claims.iat())
+            .await?;
+
+        Ok(claims)
+    }
+}
+
+impl Access {
+    pub async fn refresh(
+        &self,
+        rjti: &str,
+        aud: &str,
+        jkt: Option<String>,
+        grants: Grants,
+        refresh_ttl: usize,
+    ) -> Result<TokenResponse<PrimaryClaims>, TokenError> {
         let claims = self.claims(
             TokenParams::default()
-                .with_rjti(rjti.clone())
-                .with_ajti(ajti.clone()),
+                .with_rjti(rjti.to_owned())
+                .with_aud(aud.to_owned())
+                .with_grants(grants)
+                .with_jkt(jkt),
         );
-        let token = self.generate(&claims)?;
-        let response = TokenResponse::Access(Factory::new(claims, token));
+        let (token, value) = self.encode(&claims)?;
 
-        if params.ajti.is_some() {
-            return Ok(response);
-        }
+        self.rotate(claims.sub(), rjti, claims.jti(), &value, Some(refresh_ttl))
+            .await?;
 
-        let mut conn = self
+        Ok(TokenResponse::Access(Factory::new(claims, token)))
+    }
+
+    async fn rotate(
+        &self,
+        user_id: &str,
+        rjti: &str,
+        ajti: &str,
+        value: &str,
+        refresh_ttl: Option<usize>,
+    ) -> Result<(), TokenError> {
+        let rotated = self
             .state()
-            .get_redis_conn()
-            .await
-            .map_err(TokenError::Other)?;
-
-        let value: Option<String> = redis::cmd("GET")
-            .arg(TokenType::Refresh.get_key(&rjti))
-            .query_async(&mut conn)
-            .await
-            .map_err(|err| TokenError::Other(err.into()))?;
-
-        let _: () = redis::pipe()
-            .cmd("DEL")
-            .arg(if let Some(ref v) = value {
-                TokenType::Access.get_key(v)
-            } else {
-                String::from("no_key")
-            })
-            .ignore()
-            .cmd("SET")
-            .arg(TokenType::Refresh.get_key(&rjti))
-            .arg(&ajti)
-            .arg("KEEPTTL")
-            .ignore()
-            .cmd("SET")
-            .arg(TokenType::Access.get_key(&ajti))
-            .arg(self.user_id())
-            .arg("EX")
-            .arg(self.exp())
-            .ignore()
-            .query_async(&mut conn)
-            .await
-            .map_err(|err| TokenError::Other(err.into()))?;
-
-        Ok(response)
+            .store
+            .rotate(user_id, rjti, ajti, value, self.exp(), refresh_ttl)
+            .await?;
+        if !rotated {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "refresh token not found in redis"
+            )));
+        }
+
+        Ok(())
     }
 }
 
+/// narrows the scope of a subject token to the requested one, a subject token
+/// without a scope grants nothing so neither does the exchanged token
+fn downscope(granted: Option<&str>, requested: Option<&str>) -> Result<Option<String>, TokenError> {
+    let granted: Vec<&str> = granted.unwrap_or_default().split_whitespace().collect();
+    let scope: Vec<&str> = match requested {
+        Some(requested) => {
+            let mut scope: Vec<&str> = Vec::new();
+            for s in requested.split_whitespace() {
+                if !granted.contains(&s) {
+                    return Err(TokenError::Validation(anyhow::anyhow!(
+                        "requested scope exceeds the scope of the subject token"
+                    )));
+                }
+                if !scope.contains(&s) {
+                    scope.push(s);
+                }
+            }
+            scope
+        }
+        None => granted,
+    };
+
+    Ok((!scope.is_empty()).then(|| scope.join(" ")))
+}
+
+/// an exchanged token keeps the subject's audience unless the requested one is
+/// allowed for it in TOKEN_EXCHANGE_TARGETS
+fn retarget(
+    subject: &str,
+    requested: Option<String>,
+    targets: &HashMap<String, Vec<String>>,
+) -> Result<String, TokenError> {
+    match requested {
+        None => Ok(subject.to_owned()),
+        Some(requested) if requested == subject => Ok(requested),
+        Some(requested)
+            if targets
+                .get(subject)
+                .is_some_and(|targets| targets.contains(&requested)) =>
+        {
+            Ok(requested)
+        }
+        Some(_) => Err(TokenError::Validation(anyhow::anyhow!(
+            "subject token cannot be exchanged for the requested audience"
+        ))),
+    }
+}
+
+/// keeps only the roles whose permissions are all covered by `scope`, so a
+/// downscoped token does not carry roles implying more than it was granted
+fn backing_roles(
+    roles: Option<&[String]>,
+    permissions: &HashMap<String, Vec<String>>,
+    scope: Option<&str>,
+) -> Option<Vec<String>> {
+    let scope: Vec<&str> = scope.map(|s| s.split(' ').collect()).unwrap_or_default();
+    let roles: Vec<String> = roles
+        .unwrap_or_default()
+        .iter()
+        .filter(|role| match permissions.get(role.as_str()) {
+            Some(permissions) if !permissions.is_empty() => permissions
+                .iter()
+                .all(|permission| scope.contains(&permission.as_str())),
+            _ => false,
+        })
+        .cloned()
+        .collect();
+
+    (!roles.is_empty()).then_some(roles)
+}
+
 impl Access {
-    pub async fn refresh(&self, rjti: &str) -> Result<String, TokenError> {
-        let claims = self.claims(TokenParams::default().with_rjti(rjti.to_owned()));
-        let token = self.generate(&claims)?;
+    /// issues a token for `actor` to act on behalf of the subject, the token is
+    /// indexed under the subject's refresh token so it is revoked with it
+    pub async fn exchange(
+        &self,
+        subject: &PrimaryClaims,
+        actor: &PrimaryClaims,
+        audience: Option<String>,
+        scope: Option<String>,
+    ) -> Result<TokenResponse<PrimaryClaims>, TokenError> {
+        let scope = downscope(subject.scope(), scope.as_deref())?;
+        let aud = retarget(subject.aud(), audience, &ENV.token_exchange_targets)?;
+
+        let roles = match subject.roles.as_deref() {
+            Some(roles) if !roles.is_empty() => {
+                let permissions = database::role::get_permissions(&self.state().db, roles)
+                    .await
+                    .map_err(|err| TokenError::Other(err.into()))?;
+                backing_roles(Some(roles), &permissions, scope.as_deref())
+            }
+            _ => None,
+        };
 
-        let mut conn = self
+        let exp = ENV
+            .exchange_token_expiration
+            .min(subject.exp().saturating_sub(now()));
+        if exp == 0 {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "subject token has expired"
+            )));
+        }
+
+        let claims = PrimaryClaims::new(
+            subject.sub().to_owned(),
+            exp,
+            None,
+            Some(subject.rjti().to_owned()),
+            None,
+        )
+        .with_aud(Some(aud))
+        .with_scope(scope)
+        .with_roles(roles)
+        .with_act(Some(Actor::new(
+            actor.sub().to_owned(),
+            subject.act().cloned(),
+        )))
+        .with_cnf(subject.cnf().map(|cnf| cnf.jkt.clone()));
+        let (token, value) = self.encode(&claims)?;
+
+        let stored = self
             .state()
-            .get_redis_conn()
-            .await
-            .map_err(TokenError::Other)?;
-
-        let value: Option<String> = redis::cmd("GET")
-            .arg(TokenType::Refresh.get_key(rjti))
-            .query_async(&mut conn)
-            .await
-            .map_err(|err| TokenError::Other(err.into()))?;
-
-        let _: () = redis::pipe()
-            .cmd("DEL")
-            .arg(if let Some(ref v) = value {
-                TokenType::Access.get_key(v)
-            } else {
-                String::from("no_key")
-            })
-            .ignore()
-            .cmd("SET")
-            .arg(TokenType::Refresh.get_key(rjti))
-            .arg(claims.jti())
-            .arg("KEEPTTL")
-            .ignore()
-            .cmd("SET")
-            .arg(TokenType::Access.get_key(claims.jti()))
-            .arg(self.user_id())
-            .arg("EX")
-            .arg(self.exp())
-            .ignore()
-            .query_async(&mut conn)
-            .await
-            .map_err(|err| TokenError::Other(err.into()))?;
-
-        Ok(token)
+            .store
+            .put_exchanged(subject.rjti(), claims.jti(), &value, exp)
+            .await?;
+        if !stored {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "subject token has been revoked"
+            )));
+        }
+
+        Ok(TokenResponse::Access(Factory::new(claims, token)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Access, OPAQUE_CACHE, backing_roles, downscope, retarget};
+    use crate::token::claims::PrimaryClaims;
+    use std::{collections::HashMap, time::Instant};
+
+    fn permissions() -> HashMap<String, Vec<String>> {
+        HashMap::from([
+            (String::from("reader"), vec![String::from("read")]),
+            (
+                String::from("editor"),
+                vec![String::from("read"), String::from("write")],
+            ),
+            (String::from("member"), vec![]),
+        ])
+    }
+
+    fn roles() -> Vec<String> {
+        vec![
+            String::from("editor"),
+            String::from("member"),
+            String::from("reader"),
+        ]
+    }
+
+    #[test]
+    fn backing_roles_keeps_roles_covered_by_the_scope() {
+        assert_eq!(
+            backing_roles(Some(&roles()), &permissions(), Some("read write")),
+            Some(vec![String::from("editor"), String::from("reader")])
+        );
+    }
+
+    #[test]
+    fn backing_roles_drops_roles_implying_more_than_the_scope() {
+        assert_eq!(
+            backing_roles(Some(&roles()), &permissions(), Some("read")),
+            Some(vec![String::from("reader")])
+        );
+    }
+
+    #[test]
+    fn backing_roles_drops_everything_without_a_scope() {
+        assert_eq!(backing_roles(Some(&roles()), &permissions(), None), None);
+        assert_eq!(backing_roles(None, &permissions(), Some("read")), None);
+    }
+
+    #[test]
+    fn downscope_keeps_the_granted_scope_when_none_is_requested() {
+        assert_eq!(
+            downscope(Some("read write"), None).unwrap().as_deref(),
+            Some("read write")
+        );
+    }
+
+    #[test]
+    fn downscope_narrows_to_a_subset() {
+        assert_eq!(
+            downscope(Some("read write admin"), Some("write read write"))
+                .unwrap()
+                .as_deref(),
+            Some("write read")
+        );
+    }
+
+    #[test]
+    fn downscope_rejects_scopes_that_were_not_granted() {
+        assert!(downscope(Some("read"), Some("read write")).is_err());
+    }
+
+    #[test]
+    fn downscope_treats_a_missing_scope_as_empty() {
+        assert_eq!(downscope(None, None).unwrap(), None);
+        assert_eq!(downscope(Some(""), None).unwrap(), None);
+        assert!(downscope(None, Some("read")).is_err());
+    }
+
+    fn targets() -> HashMap<String, Vec<String>> {
+        HashMap::from([(String::from("web"), vec![String::from("api")])])
+    }
+
+    #[test]
+    fn retarget_keeps_the_subject_audience_by_default() {
+        assert_eq!(retarget("web", None, &targets()).unwrap(), "web");
+        assert_eq!(
+            retarget("billing", Some(String::from("billing")), &targets()).unwrap(),
+            "billing"
+        );
+    }
+
+    #[test]
+    fn retarget_allows_listed_audiences() {
+        assert_eq!(
+            retarget("web", Some(String::from("api")), &targets()).unwrap(),
+            "api"
+        );
+    }
+
+    #[test]
+    fn retarget_rejects_audiences_that_are_not_listed() {
+        assert!(retarget("web", Some(String::from("billing")), &targets()).is_err());
+        assert!(retarget("api", Some(String::from("web")), &targets()).is_err());
+        assert!(retarget("billing", Some(String::from("api")), &targets()).is_err());
+    }
+
+    fn claims(jti: &str, rjti: &str) -> PrimaryClaims {
+        PrimaryClaims {
+            sub: String::from("sub"),
+            jti: jti.to_owned(),
+            rjti: rjti.to_owned(),
+            exp: 0,
+            iat: 0,
+            nbf: 0,
+            custom: None,
+            iss: String::from("iss"),
+            aud: String::from("aud"),
+            scope: None,
+            roles: None,
+            act: None,
+            cnf: None,
+        }
+    }
+
+    #[test]
+    fn evict_drops_every_token_of_the_refresh_token() {
+        {
+            let mut cache = OPAQUE_CACHE.lock().unwrap();
+            for (token, jti, rjti) in [
+                ("at_evict_1", "evict_1", "evict_r1"),
+                ("at_evict_2", "evict_2", "evict_r1"),
+                ("at_evict_3", "evict_3", "evict_r2"),
+            ] {
+                cache.insert(token.to_owned(), (claims(jti, rjti), Instant::now()));
+            }
+        }
+
+        Access::evict("evict_r1");
+
+        let cache = OPAQUE_CACHE.lock().unwrap();
+        assert!(!cache.contains_key("at_evict_1"));
+        assert!(!cache.contains_key("at_evict_2"));
+        assert!(cache.contains_key("at_evict_3"));
     }
 }
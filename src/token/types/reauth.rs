@@ -1,9 +1,10 @@
 use crate::{
-    config::{ENV, state::AppState},
+    config::{ENV, env::TokenFormat, state::AppState},
     token::{
         TokenType,
         claims::PrimaryClaims,
         error::TokenError,
+        keys::Keys,
         params::TokenParams,
         response::{Factory, TokenResponse},
         traits::Token,
@@ -36,8 +37,9 @@ impl ReAuth {
             .expect("user id is required to create a reauth token")
     }
 
-    fn claims(&self) -> PrimaryClaims {
+    fn claims(&self, params: TokenParams) -> PrimaryClaims {
         PrimaryClaims::new(self.user_id().to_owned(), self.exp(), None, None, None)
+            .with_aud(params.aud)
     }
 }
 
@@ -46,26 +48,32 @@ impl Token<PrimaryClaims> for ReAuth {
         self.state.clone()
     }
 
-    fn public_key(&self) -> &[u8] {
-        &ENV.reauth_token_public_key
-    }
-    fn private_key(&self) -> &[u8] {
-        &ENV.reauth_token_private_key
+    fn keys(&self) -> &Keys {
+        &self.state.keys
     }
     fn exp(&self) -> usize {
         ENV.reauth_token_expiration
     }
+    fn token_type(&self) -> TokenType {
+        TokenType::ReAuth
+    }
+    fn format(&self) -> TokenFormat {
+        ENV.reauth_token_format
+    }
 
-    async fn create(&self, _: TokenParams) -> Result<TokenResponse<PrimaryClaims>, TokenError> {
-        let claims = self.claims();
+    async fn create(
+        &self,
+        params: TokenParams,
+    ) -> Result<TokenResponse<PrimaryClaims>, TokenError> {
+        let claims = self.claims(params);
         let token = self.generate(&claims)?;
         Ok(TokenResponse::Reauth(Factory::new(claims, token)))
     }
 
-    async fn verify(&self, token: &str, _: TokenType) -> Result<PrimaryClaims, TokenError>
+    async fn lookup(&self, claims: PrimaryClaims, _: TokenType) -> Result<PrimaryClaims, TokenError>
     where
         PrimaryClaims: Send,
     {
-        self.decode(token)
+        Ok(claims)
     }
 }
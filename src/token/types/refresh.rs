@@ -1,13 +1,17 @@
 use crate::{
-    config::{ENV, state::AppState},
+    config::{ENV, env::TokenFormat, state::AppState},
     database,
     token::{
         TokenType,
         claims::{Claims, PrimaryClaims},
         error::TokenError,
+        keys::Keys,
+        lifetime,
         params::TokenParams,
         response::{Factory, TokenResponse},
+        store::TokenStore,
         traits::Token,
+        types::access::Access,
     },
 };
 use ulid::Ulid;
@@ -44,17 +48,25 @@ impl Token<PrimaryClaims> for Refresh {
         self.state.clone()
     }
 
-    fn public_key(&self) -> &[u8] {
-        &ENV.refresh_token_public_key
-    }
-    fn private_key(&self) -> &[u8] {
-        &ENV.refresh_token_private_key
+    fn keys(&self) -> &Keys {
+        &self.state.keys
     }
+    /// the token itself lives until the absolute cap, the sliding and idle
+    /// expiry is enforced through the store ttl and the session row
     fn exp(&self) -> usize {
-        ENV.refresh_token_expiration
+        ENV.session_absolute_lifetime
+    }
+    fn token_type(&self) -> TokenType {
+        TokenType::Refresh
+    }
+    fn format(&self) -> TokenFormat {
+        ENV.refresh_token_format
     }
 
-    async fn create(&self, _: TokenParams) -> Result<TokenResponse<PrimaryClaims>, TokenError> {
+    async fn create(
+        &self,
+        params: TokenParams,
+    ) -> Result<TokenResponse<PrimaryClaims>, TokenError> {
         let ajti = Ulid::new().to_string();
         let claims = PrimaryClaims::new(
             self.user_id().to_owned(),
@@ -62,30 +74,40 @@ impl Token<PrimaryClaims> for Refresh {
             None,
             None,
             Some(ajti.clone()),
-        );
+        )
+        .with_aud(params.aud)
+        .with_cnf(params.jkt);
         let token = self.generate(&claims)?;
+        let ttl = lifetime::expires_at(params.remember_me.unwrap_or(true), claims.iat, claims.iat)
+            - claims.iat;
 
-        let mut conn = self
+        let evicted = self
             .state()
-            .get_redis_conn()
-            .await
-            .map_err(TokenError::Other)?;
-
-        let _: () = redis::pipe()
-            .cmd("SET")
-            .arg(TokenType::Refresh.get_key(claims.jti()))
-            .arg(&ajti)
-            .arg("EX")
-            .arg(self.exp())
-            .ignore()
-            .arg("SET")
-            .arg(TokenType::Access.get_key(&ajti))
-            .arg(self.user_id())
-            .arg("EX")
-            .arg(ENV.access_token_expiration)
-            .query_async(&mut conn)
-            .await
-            .map_err(|err| TokenError::Other(err.into()))?;
+            .store
+            .issue(
+                self.user_id(),
+                claims.jti(),
+                &ajti,
+                ttl,
+                ENV.access_token_expiration,
+                params.session_cap,
+            )
+            .await?
+            .ok_or(TokenError::SessionLimit(anyhow::anyhow!(
+                "too many active sessions, sign out of another device first"
+            )))?;
+
+        // the store already revoked the evicted tokens, only their cached
+        // claims and session records are left behind
+        for rjti in evicted {
+            Access::evict(&rjti);
+            if let Err(err) = database::session::delete(&self.state.db, &rjti).await {
+                log::error!(
+                    "{}",
+                    anyhow::Error::new(err).context("failed to delete the evicted session")
+                )
+            }
+        }
 
         Ok(TokenResponse::Refresh(Factory::new(claims, token)))
     }
@@ -93,35 +115,61 @@ impl Token<PrimaryClaims> for Refresh {
 
 impl Refresh {
     pub async fn delete(&self, rjti: &str) -> Result<(), TokenError> {
+        if !self.end(rjti).await? {
+            return Err(TokenError::Validation(anyhow::anyhow!(
+                "token not found in redis"
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// deletes the session record and revokes its tokens, returns false when
+    /// the refresh token had already expired or been revoked
+    pub async fn end(&self, rjti: &str) -> Result<bool, TokenError> {
         database::session::delete(&self.state.db, rjti)
             .await
             .map_err(|err| TokenError::Other(err.into()))?;
 
-        let mut conn = self
-            .state()
-            .get_redis_conn()
-            .await
-            .map_err(TokenError::Other)?;
+        revoke(&*self.state.store, rjti).await
+    }
+}
 
-        let value: Option<String> = redis::cmd("GET")
-            .arg(TokenType::Refresh.get_key(rjti))
-            .query_async(&mut conn)
-            .await
-            .map_err(|err| TokenError::Other(err.into()))?;
-        let value = value
-            .ok_or_else(|| TokenError::Validation(anyhow::anyhow!("token not found in redis")))?;
-
-        let _: () = redis::pipe()
-            .cmd("DEL")
-            .arg(TokenType::Refresh.get_key(rjti))
-            .ignore()
-            .cmd("DEL")
-            .arg(TokenType::Access.get_key(&value))
-            .ignore()
-            .query_async(&mut conn)
+/// revokes the tokens of a session and drops their cached claims, returns
+/// false when there was nothing left to revoke
+pub async fn revoke(store: &dyn TokenStore, rjti: &str) -> Result<bool, TokenError> {
+    let revoked = store.revoke(rjti).await?;
+    Access::evict(rjti);
+
+    Ok(revoked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::revoke;
+    use crate::token::{
+        TokenType,
+        store::{MemoryStore, TokenStore},
+    };
+
+    #[tokio::test]
+    async fn revoking_an_expired_session_is_not_an_error() {
+        let store = MemoryStore::default();
+        store.issue("user", "01A", "a1", 0, 0, None).await.unwrap();
+
+        assert!(!revoke(&store, "01A").await.unwrap());
+        assert_eq!(store.get(TokenType::Refresh, "01A").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn revoking_a_live_session_removes_its_tokens() {
+        let store = MemoryStore::default();
+        store
+            .issue("user", "01A", "a1", 3600, 900, None)
             .await
-            .map_err(|err| TokenError::Other(err.into()))?;
+            .unwrap();
 
-        Ok(())
+        assert!(revoke(&store, "01A").await.unwrap());
+        assert_eq!(store.get(TokenType::Access, "a1").await.unwrap(), None);
     }
 }
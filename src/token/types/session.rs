@@ -1,15 +1,18 @@
 use crate::{
-    config::{ENV, state::AppState},
+    config::{ENV, env::TokenFormat, state::AppState},
     model::user::UserDetails,
     token::{
         TokenType,
         claims::ExtendedClaims,
         error::TokenError,
+        jwe,
+        keys::Keys,
         params::TokenParams,
         response::{Factory, TokenResponse},
         traits::Token,
     },
 };
+use jsonwebtoken::Validation;
 
 pub struct Session {
     pub state: AppState,
@@ -40,26 +43,61 @@ impl Token<ExtendedClaims> for Session {
         self.state.clone()
     }
 
-    fn public_key(&self) -> &[u8] {
-        &ENV.session_token_public_key
-    }
-    fn private_key(&self) -> &[u8] {
-        &ENV.session_token_private_key
+    fn keys(&self) -> &Keys {
+        &self.state.keys
     }
     fn exp(&self) -> usize {
         ENV.session_token_expiration
     }
+    fn token_type(&self) -> TokenType {
+        TokenType::Session
+    }
+    fn format(&self) -> TokenFormat {
+        ENV.session_token_format
+    }
 
-    async fn create(&self, _: TokenParams) -> Result<TokenResponse<ExtendedClaims>, TokenError> {
-        let claims = ExtendedClaims::new(self.user(), self.exp());
+    async fn create(
+        &self,
+        params: TokenParams,
+    ) -> Result<TokenResponse<ExtendedClaims>, TokenError> {
+        let claims =
+            ExtendedClaims::new(self.user(), self.exp(), params.aud, params.grants.as_ref());
         let token = self.generate(&claims)?;
+        let token = match &self.state.keys.session_encryption {
+            Some(keys) => jwe::encrypt(&token, &keys.public)?,
+            None => token,
+        };
+
         Ok(TokenResponse::Session(Factory::new(claims, token)))
     }
 
-    async fn verify(&self, token: &str, _: TokenType) -> Result<ExtendedClaims, TokenError>
+    fn decode_with(
+        &self,
+        token: &str,
+        validation: &Validation,
+    ) -> Result<ExtendedClaims, TokenError> {
+        // signed only tokens issued before encryption was enabled stay valid
+        let token = match (&self.state.keys.session_encryption, jwe::is_jwe(token)) {
+            (Some(keys), true) => jwe::decrypt(token, &keys.private)?,
+            (None, true) => {
+                return Err(TokenError::Validation(anyhow::anyhow!(
+                    "encrypted session tokens are not enabled"
+                )));
+            }
+            (_, false) => token.to_owned(),
+        };
+
+        self.decode_signed(&token, validation)
+    }
+
+    async fn lookup(
+        &self,
+        claims: ExtendedClaims,
+        _: TokenType,
+    ) -> Result<ExtendedClaims, TokenError>
     where
         ExtendedClaims: Send,
     {
-        self.decode(token)
+        Ok(claims)
     }
 }
@@ -1,9 +1,11 @@
+pub mod user_agent;
 pub mod verify;
 
 use base64::prelude::*;
 use rand::Rng;
 use serde::{Deserialize, Deserializer};
-use std::{sync::Arc, time::SystemTime};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, sync::Arc, time::SystemTime};
 use tokio::signal::{self};
 
 use crate::{config::state::AppState, error::AppError};
@@ -64,6 +66,56 @@ where
     Ok(Arc::new(bytes))
 }
 
+pub fn deserialize_optional_base64<'de, D>(
+    deserializer: D,
+) -> Result<Option<Arc<Vec<u8>>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: Option<String> = Option::deserialize(deserializer)?;
+    s.map(|s| {
+        BASE64_STANDARD
+            .decode(s.as_bytes())
+            .map(Arc::new)
+            .map_err(serde::de::Error::custom)
+    })
+    .transpose()
+}
+
+/// parses `source:target target,source:target` into the targets of each source
+pub fn deserialize_audience_map<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = String::deserialize(deserializer)?;
+    parse_audience_map(&s).map_err(serde::de::Error::custom)
+}
+
+fn parse_audience_map(s: &str) -> Result<HashMap<String, Vec<String>>, String> {
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    for entry in s
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (source, targets) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("expected <audience>:<audience> ..., got {}", entry))?;
+        let source = source.trim();
+        if source.is_empty() {
+            return Err(format!("missing source audience in {}", entry));
+        }
+
+        map.entry(source.to_owned())
+            .or_default()
+            .extend(targets.split_whitespace().map(str::to_owned));
+    }
+
+    Ok(map)
+}
+
 pub fn deserialize_arc_str<'de, D>(deserializer: D) -> Result<Arc<str>, D::Error>
 where
     D: Deserializer<'de>,
@@ -88,6 +140,15 @@ pub fn generate_otp() -> String {
         .collect()
 }
 
+pub fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::rng().random();
+    BASE64_URL_SAFE_NO_PAD.encode(bytes)
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(secret.as_bytes()))
+}
+
 pub async fn validate_otp(state: AppState, key: &str, otp: &str) -> Result<(), AppError> {
     let mut conn = state.get_redis_conn().await.map_err(AppError::Other)?;
 
@@ -107,3 +168,26 @@ pub async fn validate_otp(state: AppState, key: &str, otp: &str) -> Result<(), A
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_audience_map;
+
+    #[test]
+    fn parses_targets_per_audience() {
+        let map = parse_audience_map("web:api billing, api:billing").unwrap();
+        assert_eq!(map["web"], ["api", "billing"]);
+        assert_eq!(map["api"], ["billing"]);
+    }
+
+    #[test]
+    fn parses_an_empty_map() {
+        assert!(parse_audience_map("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_entries_without_a_source() {
+        assert!(parse_audience_map("web").is_err());
+        assert!(parse_audience_map(":api").is_err());
+    }
+}
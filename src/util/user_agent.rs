@@ -0,0 +1,50 @@
+use crate::metrics;
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, sync::Mutex};
+use woothee::parser::Parser;
+
+/// distinct user agents kept parsed, the cache is emptied once it is full
+const CACHE_SIZE: usize = 1024;
+
+static PARSER: Lazy<Parser> = Lazy::new(Parser::new);
+static CACHE: Lazy<Mutex<HashMap<String, UserAgent>>> = Lazy::new(Default::default);
+
+#[derive(Debug, Default, Clone)]
+pub struct UserAgent {
+    pub device_vendor: Option<String>,
+    pub device_model: Option<String>,
+    pub os_name: Option<String>,
+    pub os_version: Option<String>,
+    pub browser_name: Option<String>,
+    pub browser_version: Option<String>,
+}
+
+pub fn parse(user_agent: &str) -> UserAgent {
+    let mut cache = CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(parsed) = cache.get(user_agent) {
+        metrics::USER_AGENT_CACHE_HITS.inc();
+        return parsed.clone();
+    }
+    metrics::USER_AGENT_CACHE_MISSES.inc();
+
+    let parsed = PARSER
+        .parse(user_agent)
+        .map(|result| UserAgent {
+            device_vendor: Some(result.category.to_owned()),
+            device_model: Some(result.vendor.to_owned()),
+            os_name: Some(result.os.to_owned()),
+            os_version: Some(result.os_version.to_string()),
+            browser_name: Some(result.name.to_owned()),
+            browser_version: Some(result.version.to_owned()),
+        })
+        .unwrap_or_default();
+
+    if cache.len() >= CACHE_SIZE {
+        cache.clear();
+    }
+    cache.insert(user_agent.to_owned(), parsed.clone());
+
+    parsed
+}
@@ -68,3 +68,59 @@ pub fn otp(otp: &str) -> Result<(), ValidationError> {
 
     Ok(())
 }
+
+pub fn role(role: &str) -> Result<(), ValidationError> {
+    let checks = [
+        (role.len() < 3, "must be at least 3 characters"),
+        (role.len() > 100, "must be at most 100 characters"),
+        (
+            !role.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+            "must contain only alphanumeric characters and underscores",
+        ),
+    ];
+
+    for (not_valid, message) in checks {
+        if not_valid {
+            return Err(ValidationError::new("role").with_message(Cow::Borrowed(message)));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn permissions(permissions: &[String]) -> Result<(), ValidationError> {
+    for permission in permissions {
+        let checks = [
+            (permission.is_empty(), "must not be empty"),
+            (permission.len() > 150, "must be at most 150 characters"),
+            (
+                !permission
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '_' | '.' | '-')),
+                "must contain only alphanumeric characters and : _ . -",
+            ),
+        ];
+
+        for (not_valid, message) in checks {
+            if not_valid {
+                return Err(
+                    ValidationError::new("permissions").with_message(Cow::Borrowed(message))
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn token_type_hint(hint: &str) -> Result<(), ValidationError> {
+    if !matches!(hint, "access_token" | "session_token") {
+        return Err(
+            ValidationError::new("token_type_hint").with_message(Cow::Borrowed(
+                "must be either access_token or session_token",
+            )),
+        );
+    }
+
+    Ok(())
+}